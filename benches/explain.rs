@@ -0,0 +1,31 @@
+//! The cases of the `bench` subcommand, measured by criterion.
+
+// Only part of the modules is benchmarked
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/bench.rs"]
+mod bench;
+#[path = "../src/tictactoe/mod.rs"]
+mod tictactoe;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn cases(c: &mut Criterion) {
+    let mut cache = tictactoe::MinimaxCache::new();
+    for case in &bench::CASES {
+        let mut group = c.benchmark_group(case.name);
+        group.sample_size(10);
+        for board in bench::POSITIONS {
+            let board: tictactoe::Grid = board.parse().unwrap();
+            // Warms up the minimax cache
+            (case.run)(&board, &mut cache);
+            group.bench_function(board.to_string().replace('/', " "), |b| {
+                b.iter(|| (case.run)(&board, &mut cache))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, cases);
+criterion_main!(benches);
@@ -0,0 +1,130 @@
+//! Timings of the explanation engine, to catch performance regressions.
+
+use crate::tictactoe::{self, board_text, Grid, MinimaxCache, Semivalue};
+
+use std::hint::black_box;
+
+use geng::prelude::*;
+
+/// Positions every case runs on, from the opening to the endgame.
+pub const POSITIONS: [&str; 4] = [".../.../...", "x../.o./...", "x.o/.x./o..", "xox/oxo/..."];
+
+pub struct Case {
+    pub name: &'static str,
+    /// Runs the case, with a warm minimax cache for the policy.
+    pub run: fn(&Grid, &mut MinimaxCache),
+}
+
+pub const CASES: [Case; 5] = [
+    Case {
+        name: "minimax",
+        run: minimax,
+    },
+    Case {
+        name: "possible_states",
+        run: possible_states,
+    },
+    Case {
+        name: "semivalue_with_value",
+        run: semivalue_with_value,
+    },
+    Case {
+        name: "sverl_local",
+        run: sverl_local,
+    },
+    Case {
+        name: "sverl_global",
+        run: sverl_global,
+    },
+];
+
+/// Evaluates the whole game tree, without a cache from previous runs.
+fn minimax(board: &Grid, _cache: &mut MinimaxCache) {
+    let player = board.current_player().expect("the game is over");
+    black_box(tictactoe::minimax(
+        board,
+        &mut MinimaxCache::new(),
+        player,
+        None,
+    ));
+}
+
+/// Enumerates the states consistent with observing nothing, the largest set.
+fn possible_states(board: &Grid, _cache: &mut MinimaxCache) {
+    let hidden: Vec<_> = board.positions().collect();
+    black_box(board.partial_observation(&hidden).possible_states());
+}
+
+fn semivalue_with_value(board: &Grid, _cache: &mut MinimaxCache) {
+    let mut policy = tictactoe::policy_random();
+    black_box(
+        board.semivalue_with_value(Semivalue::Shapley, |observation| {
+            observation.value(&mut policy)
+        }),
+    );
+}
+
+fn sverl_local(board: &Grid, cache: &mut MinimaxCache) {
+    let mut policy = tictactoe::policy_minimax_cached(None, cache);
+    black_box(board.sverl(false, 0.9, &mut policy, None));
+}
+
+fn sverl_global(board: &Grid, cache: &mut MinimaxCache) {
+    let mut policy = tictactoe::policy_minimax_cached(None, cache);
+    black_box(board.sverl(true, 0.9, &mut policy, None));
+}
+
+/// Timings of one case on one position, in milliseconds.
+#[derive(Debug, Serialize)]
+pub struct BenchResult {
+    pub name: String,
+    #[serde(with = "board_text")]
+    pub board: Grid,
+    pub samples: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub median: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Runs the cases whose name contains `filter`, each `samples` times after a warm-up run.
+pub fn run(filter: &str, samples: usize) -> Vec<BenchResult> {
+    let mut cache = MinimaxCache::new();
+    let mut results = Vec::new();
+    for case in CASES.iter().filter(|case| case.name.contains(filter)) {
+        for board in POSITIONS {
+            let board: Grid = board.parse().unwrap();
+            (case.run)(&board, &mut cache);
+
+            let mut times: Vec<f64> = (0..samples)
+                .map(|_| {
+                    let timer = Timer::new();
+                    (case.run)(&board, &mut cache);
+                    timer.elapsed().as_secs_f64() * 1000.0
+                })
+                .collect();
+            times.sort_by_key(|&time| r64(time));
+
+            let n = times.len() as f64;
+            let mean = times.iter().sum::<f64>() / n;
+            let variance = times.iter().map(|time| (time - mean).powi(2)).sum::<f64>() / n;
+            let result = BenchResult {
+                name: case.name.to_string(),
+                board,
+                samples,
+                mean,
+                std_dev: variance.sqrt(),
+                median: times[times.len() / 2],
+                min: times[0],
+                max: times[times.len() - 1],
+            };
+            eprintln!(
+                "{:<24} {}  {:>10.3}ms ± {:.3}ms",
+                result.name, result.board, result.median, result.std_dev
+            );
+            results.push(result);
+        }
+    }
+    results
+}
@@ -0,0 +1,787 @@
+use crate::{
+    bench, export, repl, server, terminal,
+    tictactoe::{
+        self, AggregateProgress, Database, DatabaseEntry, Explanation, ExplanationMethod,
+        ExplanationParameters, GameRecord, Grid, LimeSampling, MinimaxCache, Owen, Partition,
+        Player, PlayerRecord, Policy, Semivalue, DATABASE_VERSION,
+    },
+};
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use geng::prelude::*;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PolicyArg {
+    Random,
+    Minimax,
+}
+
+impl PolicyArg {
+    /// The name accepted on the command line.
+    pub fn name(self) -> String {
+        clap::ValueEnum::to_possible_value(&self)
+            .expect("policies are not skipped")
+            .get_name()
+            .to_owned()
+    }
+
+    /// The parameters recorded with explanations of the policy.
+    pub fn parameters(
+        self,
+        depth: Option<usize>,
+        opponent: Option<PolicyArg>,
+        weighting: Semivalue,
+        gamma: f64,
+    ) -> ExplanationParameters {
+        let minimax =
+            matches!(self, PolicyArg::Minimax) || matches!(opponent, Some(PolicyArg::Minimax));
+        ExplanationParameters {
+            policy: self.name(),
+            minimax_depth: if minimax { depth } else { None },
+            opponent: opponent.map(PolicyArg::name),
+            weighting,
+            gamma,
+            lime: None,
+            partition: None,
+            hidden: None,
+        }
+    }
+
+    pub fn build(self, depth: Option<usize>, minimax_cache: &mut MinimaxCache) -> Policy<'_> {
+        match self {
+            PolicyArg::Random => tictactoe::policy_random(),
+            PolicyArg::Minimax => tictactoe::policy_minimax_cached(depth, minimax_cache),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum MethodArg {
+    Shapley,
+    SverlLocal,
+    SverlGlobal,
+    /// Contributions to the state value of the policy.
+    StateValue,
+    /// Owen values of the policy.
+    Owen,
+    /// Owen values of SVERL-P local.
+    OwenSverl,
+    /// Change of the policy when a single cell is hidden, a baseline.
+    Occlusion,
+    /// Coefficients of a LIME surrogate of the policy, a baseline.
+    Lime,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum CounterfactualTarget {
+    /// The actions the policy is most likely to take.
+    TopAction,
+    /// The outcome the minimax search expects for the player to move.
+    Minimax,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum ColorArg {
+    /// Colors if the terminal supports truecolor.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorArg {
+    fn enabled(self) -> bool {
+        match self {
+            ColorArg::Auto => terminal::supports_truecolor(),
+            ColorArg::Always => true,
+            ColorArg::Never => false,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct ExplainArgs {
+    /// The board, row by row from the top, e.g. `x.o/.x./...`.
+    #[clap(default_value = ".../.../...")]
+    board: Grid,
+    #[clap(long, value_enum, default_value_t = PolicyArg::Minimax)]
+    policy: PolicyArg,
+    /// Depth limit of the minimax search, unlimited if not set.
+    #[clap(long)]
+    depth: Option<usize>,
+    /// Policy of the other player in SVERL-P rollouts, the explained policy if not set.
+    #[clap(long, value_enum)]
+    opponent: Option<PolicyArg>,
+    #[clap(long, value_enum, default_value_t = MethodArg::SverlLocal)]
+    method: MethodArg,
+    /// Weighting of coalitions for Shapley, SVERL-P and state values:
+    /// `shapley`, `banzhaf`, or `beta:<alpha>,<beta>`.
+    #[clap(long, default_value_t = Semivalue::Shapley)]
+    weighting: Semivalue,
+    /// Discount factor used by SVERL-P.
+    #[clap(long, default_value_t = 0.9, value_parser = parse_gamma)]
+    gamma: f64,
+    /// Groups of cells for Owen values: `rows`, `columns`, `rings`,
+    /// or cell indices in reading order, e.g. `0,1,2;3,4,5;6,7,8`.
+    #[clap(long, default_value = "rows")]
+    groups: Partition,
+    /// Random observations the LIME surrogate is fitted on.
+    #[clap(long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+    samples: u64,
+    /// Seed of the observations of LIME, random if not set.
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Whether to color the values in the terminal like the GUI.
+    #[clap(long, value_enum, default_value_t = ColorArg::Auto)]
+    color: ColorArg,
+    /// Write the explanation with what it was computed with as JSON.
+    #[clap(long)]
+    json: Option<std::path::PathBuf>,
+}
+
+/// The result of [ExplainArgs].
+enum Explained {
+    Policy(Box<Explanation<Grid<Grid<f64>>>>),
+    Cells(Box<Explanation<Grid<f64>>>),
+    OwenPolicy(Box<Explanation<Owen<Grid<f64>>>>),
+    OwenCells(Box<Explanation<Owen<f64>>>),
+}
+
+impl ExplainArgs {
+    fn explain(&self) -> Explained {
+        let mut cache = BTreeMap::new();
+        let policy = &mut self.policy.build(self.depth, &mut cache);
+        let mut opponent_cache = BTreeMap::new();
+        let mut opponent = self
+            .opponent
+            .map(|opponent| opponent.build(self.depth, &mut opponent_cache));
+
+        let board = &self.board;
+        let parameters =
+            self.policy
+                .parameters(self.depth, self.opponent, self.weighting, self.gamma);
+        match self.method {
+            MethodArg::Shapley => {
+                Explained::Policy(Box::new(board.explain_policy(&parameters, policy)))
+            }
+            MethodArg::SverlLocal => Explained::Cells(Box::new(board.explain_sverl(
+                false,
+                &parameters,
+                policy,
+                opponent.as_mut(),
+            ))),
+            MethodArg::SverlGlobal => Explained::Cells(Box::new(board.explain_sverl(
+                true,
+                &parameters,
+                policy,
+                opponent.as_mut(),
+            ))),
+            MethodArg::StateValue => {
+                Explained::Cells(Box::new(board.explain_state_value(&parameters, policy)))
+            }
+            MethodArg::Owen => Explained::OwenPolicy(Box::new(board.explain_owen(
+                &parameters,
+                &self.groups,
+                policy,
+            ))),
+            MethodArg::OwenSverl => Explained::OwenCells(Box::new(board.explain_sverl_owen(
+                &parameters,
+                &self.groups,
+                policy,
+                opponent.as_mut(),
+            ))),
+            MethodArg::Occlusion => {
+                Explained::Policy(Box::new(board.explain_occlusion(&parameters, policy)))
+            }
+            MethodArg::Lime => {
+                let (seed, _) = tictactoe::seeded_rng(self.seed);
+                let sampling = LimeSampling {
+                    samples: self.samples as usize,
+                    seed,
+                };
+                Explained::Policy(Box::new(board.explain_lime(&parameters, sampling, policy)))
+            }
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct CounterfactualArgs {
+    /// The board, row by row from the top, e.g. `x.o/.x./...`.
+    board: Grid,
+    #[clap(long, value_enum, default_value_t = PolicyArg::Minimax)]
+    policy: PolicyArg,
+    /// Depth limit of the minimax search, unlimited if not set.
+    #[clap(long)]
+    depth: Option<usize>,
+    /// What the changes to the board have to alter.
+    #[clap(long, value_enum, default_value_t = CounterfactualTarget::TopAction)]
+    target: CounterfactualTarget,
+    /// Whether to color the values in the terminal like the GUI.
+    #[clap(long, value_enum, default_value_t = ColorArg::Auto)]
+    color: ColorArg,
+}
+
+#[derive(clap::Args)]
+pub struct AggregateArgs {
+    #[clap(long, value_enum, default_value_t = PolicyArg::Minimax)]
+    policy: PolicyArg,
+    /// Depth limit of the minimax search, unlimited if not set.
+    #[clap(long)]
+    depth: Option<usize>,
+    /// Policy of the other player, the explained policy if not set.
+    #[clap(long, value_enum)]
+    opponent: Option<PolicyArg>,
+    /// Discount factor used by SVERL-P.
+    #[clap(long, default_value_t = 0.9, value_parser = parse_gamma)]
+    gamma: f64,
+    /// Use SVERL-P global instead of local.
+    #[clap(long)]
+    global: bool,
+    /// Write the mean and the per-state contributions as JSON.
+    #[clap(long)]
+    output: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct PrecomputeArgs {
+    #[clap(long, value_enum, default_value_t = PolicyArg::Minimax)]
+    policy: PolicyArg,
+    /// Depth limit of the minimax search, unlimited if not set.
+    #[clap(long)]
+    depth: Option<usize>,
+    /// Discount factor used by SVERL-P.
+    #[clap(long, default_value_t = 0.9, value_parser = parse_gamma)]
+    gamma: f64,
+    /// Number of worker threads, all available cores if not set.
+    #[clap(long)]
+    threads: Option<std::num::NonZeroUsize>,
+    /// Where to write the database, `assets/database/<policy>.json` by default,
+    /// which is where the GUI looks for it.
+    #[clap(long)]
+    output: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct TournamentArgs {
+    /// Policy playing X.
+    #[clap(long, value_enum, default_value_t = PolicyArg::Minimax)]
+    x: PolicyArg,
+    /// Depth limit of X's minimax search, unlimited if not set.
+    #[clap(long)]
+    x_depth: Option<usize>,
+    /// Policy playing O.
+    #[clap(long, value_enum, default_value_t = PolicyArg::Random)]
+    o: PolicyArg,
+    /// Depth limit of O's minimax search, unlimited if not set.
+    #[clap(long)]
+    o_depth: Option<usize>,
+    #[clap(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    games: u64,
+    /// Positions to start from, used in turn. The empty board if not set.
+    #[clap(long = "opening")]
+    openings: Vec<Grid>,
+    /// Seed for the moves, random if not set.
+    /// Each game is seeded with the following number, so it can be replayed on its own.
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Write all games as a game record file, which the GUI can replay.
+    #[clap(long)]
+    records: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct ExportArgs {
+    #[clap(flatten)]
+    explain: ExplainArgs,
+    /// Where to write the figure, a PNG image if it ends with `.png` and SVG otherwise.
+    /// Shapley and Owen values are drawn as one board per cell.
+    #[clap(long, short)]
+    output: std::path::PathBuf,
+    /// Write the value of each cell on the board.
+    #[clap(long)]
+    labels: bool,
+    /// Size of PNG images, 1 is 100 pixels per cell.
+    #[clap(long, default_value_t = 1.0)]
+    scale: f32,
+}
+
+#[derive(clap::Args)]
+pub struct ReplArgs {
+    /// The board to start from, row by row from the top.
+    #[clap(default_value = ".../.../...")]
+    board: Grid,
+    #[clap(long, value_enum, default_value_t = PolicyArg::Minimax)]
+    policy: PolicyArg,
+    /// Depth limit of the minimax search, unlimited if not set.
+    #[clap(long)]
+    depth: Option<usize>,
+    /// Discount factor used by SVERL-P.
+    #[clap(long, default_value_t = 0.9, value_parser = parse_gamma)]
+    gamma: f64,
+    /// Whether to color the values in the terminal like the GUI.
+    #[clap(long, value_enum, default_value_t = ColorArg::Auto)]
+    color: ColorArg,
+}
+
+#[derive(clap::Args)]
+pub struct BenchArgs {
+    /// Only run the cases whose name contains this, e.g. `sverl`.
+    #[clap(default_value = "")]
+    filter: String,
+    /// Timed runs of every case on every position.
+    #[clap(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    samples: u64,
+    /// Write the timings as JSON to this file instead of the standard output.
+    #[clap(long)]
+    output: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    /// Address to listen on, only reachable from this machine by default.
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    address: String,
+}
+
+/// A discount factor in `[0, 1]`.
+pub fn parse_gamma(s: &str) -> Result<f64, String> {
+    match s.trim().parse() {
+        Ok(gamma) if (0.0..=1.0).contains(&gamma) => Ok(gamma),
+        _ => Err(format!(
+            "invalid gamma {:?}, expected a number in [0, 1]",
+            s
+        )),
+    }
+}
+
+pub fn explain(args: &ExplainArgs) {
+    let board = &args.board;
+    println!("board: {}", board);
+
+    let config = args.color.enabled().then(crate::Config::read);
+    let palette = config.as_ref().map(|config| &config.palette);
+    let print = |lines: Vec<String>| {
+        for line in lines {
+            println!("{}", line);
+        }
+    };
+
+    let mut timer = Timer::new();
+    let explained = args.explain();
+    match &explained {
+        Explained::Policy(explanation) => {
+            match explanation.parameters.lime {
+                Some(sampling) => println!(
+                    "{} ({} samples, seed {}):",
+                    explanation.method, sampling.samples, sampling.seed
+                ),
+                None if explanation.method == ExplanationMethod::Shapley => {
+                    println!("{}:", args.weighting)
+                }
+                None => println!("{}:", explanation.method),
+            }
+            print(terminal::small_multiples_lines(
+                palette,
+                board,
+                &explanation.values,
+            ));
+            println!("{}", efficiency(explanation));
+        }
+        Explained::Cells(explanation) => {
+            println!("{}:", explanation.method);
+            print(terminal::board_lines(palette, board, &explanation.values));
+            println!("{}", efficiency(explanation));
+        }
+        Explained::OwenPolicy(explanation) => {
+            println!(
+                "{} groups: {:?}",
+                explanation.method, explanation.values.groups
+            );
+            println!("cells:");
+            print(terminal::small_multiples_lines(
+                palette,
+                board,
+                &explanation.values.cells,
+            ));
+            println!("{}", efficiency(explanation));
+        }
+        Explained::OwenCells(explanation) => {
+            println!(
+                "{} groups: {:?}",
+                explanation.method, explanation.values.groups
+            );
+            println!("cells:");
+            print(terminal::board_lines(
+                palette,
+                board,
+                &explanation.values.cells,
+            ));
+            println!("{}", efficiency(explanation));
+        }
+    }
+    println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
+
+    if let Some(path) = &args.json {
+        write_explanation(path, &explained);
+    }
+}
+
+/// Describes the efficiency residual of the explanation.
+pub fn efficiency<T>(explanation: &Explanation<T>) -> String {
+    let note = if explanation.is_efficient() {
+        ""
+    } else {
+        " (the method is not efficient)"
+    };
+    format!(
+        "efficiency residual: {:.2e}{}",
+        explanation.efficiency_residual, note
+    )
+}
+
+fn write_explanation(path: &std::path::Path, explained: &Explained) {
+    let file = std::fs::File::create(path).expect("failed to create output file");
+    let writer = std::io::BufWriter::new(file);
+    let result = match explained {
+        Explained::Policy(explanation) => serde_json::to_writer_pretty(writer, explanation),
+        Explained::Cells(explanation) => serde_json::to_writer_pretty(writer, explanation),
+        Explained::OwenPolicy(explanation) => serde_json::to_writer_pretty(writer, explanation),
+        Explained::OwenCells(explanation) => serde_json::to_writer_pretty(writer, explanation),
+    };
+    result.expect("failed to write explanation");
+    println!("written to {}", path.display());
+}
+
+pub fn counterfactual(args: &CounterfactualArgs) {
+    let board = &args.board;
+    println!("board: {}", board);
+    let Some(player) = board.current_player() else {
+        println!("the game is over");
+        return;
+    };
+
+    let config = args.color.enabled().then(crate::Config::read);
+    let palette = config.as_ref().map(|config| &config.palette);
+    let mut cache = BTreeMap::new();
+
+    let mut timer = Timer::new();
+    // Describes a board and what is decided on it, with the values to show
+    let decision = |board: &Grid, cache: &mut MinimaxCache| -> (String, Grid<f64>) {
+        match args.target {
+            CounterfactualTarget::TopAction => {
+                let mut policy = args.policy.build(args.depth, cache);
+                let actions = board.top_actions(&mut policy);
+                let actions: Vec<_> = actions
+                    .into_iter()
+                    .map(|action| cell_index(board, action).to_string())
+                    .collect();
+                let text = format!(
+                    "top actions of {}: {}",
+                    args.policy.name(),
+                    actions.join(", ")
+                );
+                (text, policy(board))
+            }
+            CounterfactualTarget::Minimax => {
+                let value = tictactoe::minimax_value(board, cache, player, args.depth);
+                let values = tictactoe::minimax(board, cache, player, args.depth);
+                (
+                    format!("minimax value for {:?}: {:+.2}", player, value),
+                    values,
+                )
+            }
+        }
+    };
+
+    let (text, _) = decision(board, &mut cache);
+    println!("{}", text);
+    let counterfactuals = match args.target {
+        CounterfactualTarget::TopAction => {
+            let mut policy = args.policy.build(args.depth, &mut cache);
+            board.top_action_counterfactuals(&mut policy)
+        }
+        CounterfactualTarget::Minimax => board.minimax_counterfactuals(&mut cache, args.depth),
+    };
+    match counterfactuals.first() {
+        Some(first) => println!(
+            "{} counterfactuals with {} edits",
+            counterfactuals.len(),
+            first.edits.len()
+        ),
+        None => println!("no legal board with {:?} to play changes it", player),
+    }
+    for counterfactual in &counterfactuals {
+        let edits: Vec<_> = counterfactual
+            .edits
+            .iter()
+            .map(|edit| edit.to_string())
+            .collect();
+        println!();
+        println!("{}: {}", edits.join(", "), counterfactual.board);
+        let (text, values) = decision(&counterfactual.board, &mut cache);
+        for line in terminal::board_lines(palette, &counterfactual.board, &values) {
+            println!("{}", line);
+        }
+        println!("{}", text);
+    }
+    println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
+}
+
+/// Index of the cell in reading order, as accepted on the command line.
+fn cell_index(board: &Grid, pos: vec2<tictactoe::Coord>) -> usize {
+    board
+        .reading_order()
+        .position(|other| other == pos)
+        .expect("cell outside the grid")
+}
+
+pub fn aggregate(args: &AggregateArgs) {
+    let mut cache = BTreeMap::new();
+    let mut policy = args.policy.build(args.depth, &mut cache);
+    let mut opponent_cache = BTreeMap::new();
+    let mut opponent = args
+        .opponent
+        .map(|opponent| opponent.build(args.depth, &mut opponent_cache));
+
+    let mut timer = Timer::new();
+    let mut progress =
+        AggregateProgress::new(args.global, args.gamma, &mut policy, opponent.as_mut());
+    while progress.step(&mut policy, opponent.as_mut()) {
+        if progress.done().is_multiple_of(100) {
+            eprintln!("explained {}/{} states", progress.done(), progress.total());
+        }
+    }
+    let aggregate = progress.finish();
+    println!("states: {}", aggregate.states.len());
+    println!("mean: {:?}", aggregate.mean);
+    println!("mean abs: {:?}", aggregate.mean_abs);
+    println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
+
+    if let Some(path) = &args.output {
+        let file = std::fs::File::create(path).expect("failed to create output file");
+        serde_json::to_writer(std::io::BufWriter::new(file), &aggregate)
+            .expect("failed to write aggregate");
+        println!("written to {}", path.display());
+    }
+}
+
+pub fn precompute(args: &PrecomputeArgs) {
+    let states = tictactoe::reachable_states();
+    let threads = args
+        .threads
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, std::num::NonZeroUsize::get);
+    println!("explaining {} states on {} threads", states.len(), threads);
+
+    let mut timer = Timer::new();
+    let done = AtomicUsize::new(0);
+    let entries = std::thread::scope(|scope| {
+        // Interleaved, since states with few pieces take the longest
+        let workers: Vec<_> = (0..threads)
+            .map(|worker| {
+                let states = &states;
+                let done = &done;
+                scope.spawn(move || {
+                    let parameters =
+                        args.policy
+                            .parameters(args.depth, None, Semivalue::Shapley, args.gamma);
+                    let mut cache = BTreeMap::new();
+                    let mut policy = args.policy.build(args.depth, &mut cache);
+                    states
+                        .iter()
+                        .skip(worker)
+                        .step_by(threads)
+                        .map(|board| {
+                            let entry = DatabaseEntry::new(board, &parameters, &mut policy);
+                            let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                            if done.is_multiple_of(100) {
+                                eprintln!("explained {}/{} states", done, states.len());
+                            }
+                            (board.to_string(), entry)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("worker panicked"))
+            .collect()
+    });
+    println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
+
+    let database = Database {
+        version: DATABASE_VERSION,
+        policy: args.policy.name(),
+        gamma: args.gamma,
+        minimax_depth: args.depth,
+        entries,
+    };
+    let path = args.output.clone().unwrap_or_else(|| {
+        run_dir()
+            .join("assets")
+            .join("database")
+            .join(format!("{}.json", database.policy))
+    });
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).expect("failed to create output directory");
+    }
+    let file = std::fs::File::create(&path).expect("failed to create output file");
+    serde_json::to_writer(std::io::BufWriter::new(file), &database)
+        .expect("failed to write database");
+    println!("written to {}", path.display());
+}
+
+pub fn tournament(args: &TournamentArgs) {
+    let (seed, _) = tictactoe::seeded_rng(args.seed);
+    // Separate caches, as both policies hold theirs for the whole tournament
+    let mut x_cache = BTreeMap::new();
+    let mut o_cache = BTreeMap::new();
+    let mut x = args.x.build(args.x_depth, &mut x_cache);
+    let mut o = args.o.build(args.o_depth, &mut o_cache);
+    let openings = if args.openings.is_empty() {
+        vec![Grid::new()]
+    } else {
+        args.openings.clone()
+    };
+
+    let mut timer = Timer::new();
+    let games: Vec<_> = (0..args.games as usize)
+        .map(|i| {
+            let (seed, mut rng) = tictactoe::seeded_rng(Some(seed.wrapping_add(i as u64)));
+            let opening = &openings[i % openings.len()];
+            let moves = tictactoe::play_game(opening, &mut x, &mut o, &mut rng);
+            let player = |policy: PolicyArg, depth| PlayerRecord {
+                policy: policy.name(),
+                minimax_depth: depth,
+            };
+            GameRecord::new(
+                opening.clone(),
+                moves,
+                player(args.x, args.x_depth),
+                player(args.o, args.o_depth),
+                Some(seed),
+            )
+        })
+        .collect();
+    println!("seed: {}", seed);
+    println!("games: {}", games.len());
+
+    for (name, winner) in [
+        ("x wins", Some(Player::X)),
+        ("draws", None),
+        ("o wins", Some(Player::O)),
+    ] {
+        let count = games.iter().filter(|game| game.winner == winner).count();
+        let (low, high) = wilson_interval(count, games.len());
+        println!(
+            "{}: {:.3} (95% CI {:.3} - {:.3})",
+            name,
+            count as f64 / games.len() as f64,
+            low,
+            high
+        );
+    }
+
+    let lengths: Vec<f64> = games.iter().map(|game| game.moves.len() as f64).collect();
+    let (mean, margin) = mean_interval(&lengths);
+    println!(
+        "average length: {:.2} (95% CI {:.2} - {:.2})",
+        mean,
+        mean - margin,
+        mean + margin
+    );
+    println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
+
+    if let Some(path) = &args.records {
+        let file = std::fs::File::create(path).expect("failed to create records file");
+        serde_json::to_writer(std::io::BufWriter::new(file), &games)
+            .expect("failed to write records");
+        println!("written to {}", path.display());
+    }
+}
+
+pub fn export(args: &ExportArgs) {
+    let config = crate::Config::read();
+    let palette = &config.palette;
+    let board = &args.explain.board;
+    let explained = args.explain.explain();
+    let svg = match &explained {
+        Explained::Policy(explanation) => {
+            export::small_multiples_svg(palette, board, &explanation.values, args.labels)
+        }
+        Explained::Cells(explanation) => {
+            export::board_svg(palette, board, &explanation.values, args.labels)
+        }
+        Explained::OwenPolicy(explanation) => {
+            export::small_multiples_svg(palette, board, &explanation.values.cells, args.labels)
+        }
+        Explained::OwenCells(explanation) => {
+            export::board_svg(palette, board, &explanation.values.cells, args.labels)
+        }
+    };
+    export::save(&svg, &args.output, args.scale);
+    println!("written to {}", args.output.display());
+
+    if let Some(path) = &args.explain.json {
+        write_explanation(path, &explained);
+    }
+}
+
+pub fn repl(args: &ReplArgs) {
+    repl::Repl::new(
+        args.board.clone(),
+        args.policy,
+        args.depth,
+        args.gamma,
+        args.color.enabled(),
+    )
+    .run();
+}
+
+pub fn bench(args: &BenchArgs) {
+    let results = bench::run(&args.filter, args.samples as usize);
+    match &args.output {
+        Some(path) => {
+            let file = std::fs::File::create(path).expect("failed to create output file");
+            serde_json::to_writer_pretty(std::io::BufWriter::new(file), &results)
+                .expect("failed to write timings");
+            println!("written to {}", path.display());
+        }
+        None => println!(
+            "{}",
+            serde_json::to_string_pretty(&results).expect("failed to serialize timings")
+        ),
+    }
+}
+
+pub fn serve(args: &ServeArgs) {
+    server::Server::default().run(&args.address);
+}
+
+/// Quantile of the standard normal distribution for 95% confidence intervals.
+const Z_95: f64 = 1.96;
+
+/// Wilson score interval of a proportion.
+fn wilson_interval(successes: usize, trials: usize) -> (f64, f64) {
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = Z_95 * Z_95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+/// Sample mean and the margin of its normal approximation interval.
+fn mean_interval(samples: &[f64]) -> (f64, f64) {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    if samples.len() < 2 {
+        return (mean, 0.0);
+    }
+    let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, Z_95 * (variance / n).sqrt())
+}
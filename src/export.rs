@@ -0,0 +1,280 @@
+use crate::{
+    tictactoe::{Coord, Grid, Tile},
+    Palette,
+};
+
+use std::fmt::Write;
+
+use geng::prelude::*;
+
+/// Size of a cell in SVG units, which are pixels in PNG images at scale 1.
+const CELL: f32 = 100.0;
+/// Space around and between boards, in cells.
+const MARGIN: f32 = 0.5;
+
+/// Draws the board with the contribution of each cell as a heatmap, like the GUI.
+pub fn board_svg(palette: &Palette, board: &Grid, values: &Grid<f64>, labels: bool) -> String {
+    let size = board.bounds().size().map(|x| x as f32) + vec2::splat(2.0 * MARGIN);
+    let mut svg = Svg::new(palette, size);
+    svg.board(board, values, vec2::splat(MARGIN), None, labels);
+    svg.finish()
+}
+
+/// Draws the contributions of each feature to each action, on a board placed like the feature.
+pub fn small_multiples_svg(
+    palette: &Palette,
+    board: &Grid,
+    values: &Grid<Grid<f64>>,
+    labels: bool,
+) -> String {
+    let board_size = board.bounds().size().map(|x| x as f32);
+    let step = board_size + vec2::splat(MARGIN);
+    let size = vec2(step.x * board_size.x, step.y * board_size.y) + vec2::splat(MARGIN);
+    let mut svg = Svg::new(palette, size);
+    for feature in board.positions() {
+        let offset = vec2(
+            MARGIN + feature.x as f32 * step.x,
+            MARGIN + feature.y as f32 * step.y,
+        );
+        let values = values.get(feature).expect("feature outside the grid");
+        svg.board(board, values, offset, Some(feature), labels);
+    }
+    svg.finish()
+}
+
+/// Writes the figure as a PNG image if the path ends with `.png`, and as SVG otherwise.
+pub fn save(svg: &str, path: &std::path::Path, scale: f32) {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => {
+            let mut options = resvg::usvg::Options::default();
+            let fonts = options.fontdb_mut();
+            fonts.load_system_fonts();
+            // The generic family defaults to Arial, which many systems lack
+            let sans = fonts
+                .faces()
+                .flat_map(|face| &face.families)
+                .map(|(family, _)| family.clone())
+                .find(|family| family.contains("Sans"));
+            if let Some(family) = sans {
+                fonts.set_sans_serif_family(family);
+            }
+            let tree =
+                resvg::usvg::Tree::from_str(svg, &options).expect("failed to parse the figure");
+            let size = tree
+                .size()
+                .to_int_size()
+                .scale_by(scale)
+                .expect("invalid image scale");
+            let mut pixmap = resvg::tiny_skia::Pixmap::new(size.width(), size.height())
+                .expect("failed to allocate the image");
+            resvg::render(
+                &tree,
+                resvg::tiny_skia::Transform::from_scale(scale, scale),
+                &mut pixmap.as_mut(),
+            );
+            pixmap.save_png(path).expect("failed to write the image");
+        }
+        _ => std::fs::write(path, svg).expect("failed to write the figure"),
+    }
+}
+
+/// An SVG document in the coordinates of the GUI: in cells, with the y axis pointing up.
+struct Svg<'a> {
+    palette: &'a Palette,
+    size: vec2<f32>,
+    body: String,
+}
+
+impl<'a> Svg<'a> {
+    fn new(palette: &'a Palette, size: vec2<f32>) -> Self {
+        let mut svg = Self {
+            palette,
+            size,
+            body: String::new(),
+        };
+        svg.rect(size / 2.0, size, palette.background);
+        svg
+    }
+
+    fn point(&self, pos: vec2<f32>) -> vec2<f32> {
+        vec2(pos.x * CELL, (self.size.y - pos.y) * CELL)
+    }
+
+    fn rect(&mut self, center: vec2<f32>, size: vec2<f32>, color: Rgba<f32>) {
+        let corner = self.point(center + vec2(-size.x, size.y) / 2.0);
+        writeln!(
+            self.body,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+            corner.x,
+            corner.y,
+            size.x * CELL,
+            size.y * CELL,
+            fill(color),
+        )
+        .unwrap();
+    }
+
+    fn outline(&mut self, center: vec2<f32>, size: vec2<f32>, width: f32, color: Rgba<f32>) {
+        let corner = self.point(center + vec2(-size.x, size.y) / 2.0);
+        writeln!(
+            self.body,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" {}/>"#,
+            corner.x,
+            corner.y,
+            size.x * CELL,
+            size.y * CELL,
+            stroke(color, width),
+        )
+        .unwrap();
+    }
+
+    fn line(&mut self, a: vec2<f32>, b: vec2<f32>, width: f32, color: Rgba<f32>) {
+        let (a, b) = (self.point(a), self.point(b));
+        writeln!(
+            self.body,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {}/>"#,
+            a.x,
+            a.y,
+            b.x,
+            b.y,
+            stroke(color, width),
+        )
+        .unwrap();
+    }
+
+    fn ring(&mut self, center: vec2<f32>, radius: f32, width: f32, color: Rgba<f32>) {
+        let center = self.point(center);
+        writeln!(
+            self.body,
+            r#"<circle cx="{}" cy="{}" r="{}" fill="none" {}/>"#,
+            center.x,
+            center.y,
+            radius * CELL,
+            stroke(color, width),
+        )
+        .unwrap();
+    }
+
+    fn text(&mut self, center: vec2<f32>, size: f32, text: &str, color: Rgba<f32>) {
+        let center = self.point(center);
+        writeln!(
+            self.body,
+            r#"<text x="{}" y="{}" font-size="{}" font-family="sans-serif" text-anchor="middle" dominant-baseline="central" {}>{}</text>"#,
+            center.x,
+            center.y,
+            size * CELL,
+            fill(color),
+            text,
+        )
+        .unwrap();
+    }
+
+    /// Draws the board with its bottom left corner at `offset`, see `State::draw_board`.
+    fn board(
+        &mut self,
+        board: &Grid,
+        values: &Grid<f64>,
+        offset: vec2<f32>,
+        highlight: Option<vec2<Coord>>,
+        labels: bool,
+    ) {
+        let palette = self.palette;
+        let bounds = board.bounds().map(|x| x as f32);
+
+        for pos in board.positions() {
+            let center = pos.map(|x| x as f32) + offset + vec2::splat(0.5);
+            let value = *values.get(pos).unwrap() as f32;
+            let mut color = if value > 0.0 {
+                palette.eval_positive
+            } else {
+                palette.eval_negative
+            };
+            color.a = value.abs().min(1.0);
+            self.rect(center, vec2::splat(0.9), color);
+        }
+
+        // Grid lines
+        for x in 1..board.bounds().width() {
+            let x = x as f32 + offset.x;
+            self.line(
+                vec2(x, bounds.min.y + offset.y),
+                vec2(x, bounds.max.y + offset.y),
+                0.1,
+                palette.grid,
+            );
+        }
+        for y in 1..board.bounds().height() {
+            let y = y as f32 + offset.y;
+            self.line(
+                vec2(bounds.min.x + offset.x, y),
+                vec2(bounds.max.x + offset.x, y),
+                0.1,
+                palette.grid,
+            );
+        }
+
+        for pos in board.positions() {
+            let center = pos.map(|x| x as f32) + offset + vec2::splat(0.5);
+            match board.get(pos).unwrap() {
+                Tile::Empty => {}
+                Tile::X => {
+                    let r = 0.35;
+                    self.line(center - vec2(r, r), center + vec2(r, r), 0.1, palette.grid);
+                    self.line(
+                        center + vec2(-r, r),
+                        center + vec2(r, -r),
+                        0.1,
+                        palette.grid,
+                    );
+                }
+                Tile::O => {
+                    // Same cut as in the GUI
+                    let outer = 0.45 * 0.9;
+                    let inner = outer * 0.9;
+                    self.ring(center, (outer + inner) / 2.0, outer - inner, palette.grid);
+                }
+            }
+            if labels {
+                let value = values.get(pos).unwrap();
+                let text = format!("{:+.2}", value);
+                self.text(center - vec2(0.0, 0.39), 0.12, &text, palette.text);
+            }
+            if highlight == Some(pos) {
+                self.outline(center, vec2::splat(0.9), 0.05, palette.button_border_active);
+            }
+        }
+    }
+
+    fn finish(self) -> String {
+        let size = self.point(vec2(self.size.x, 0.0));
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n{}</svg>\n",
+            self.body,
+            w = size.x,
+            h = size.y,
+        )
+    }
+}
+
+fn fill(color: Rgba<f32>) -> String {
+    format!(r#"fill="{}" fill-opacity="{}""#, hex(color), color.a)
+}
+
+fn stroke(color: Rgba<f32>, width: f32) -> String {
+    format!(
+        r#"stroke="{}" stroke-opacity="{}" stroke-width="{}""#,
+        hex(color),
+        color.a,
+        width * CELL
+    )
+}
+
+fn hex(color: Rgba<f32>) -> String {
+    let channel = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
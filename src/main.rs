@@ -1,5 +1,11 @@
+mod bench;
+mod cli;
 mod controls;
+mod export;
+mod repl;
+mod server;
 mod state;
+mod terminal;
 mod tictactoe;
 
 use geng::prelude::*;
@@ -8,6 +14,12 @@ use geng::prelude::*;
 struct Opts {
     #[clap(subcommand)]
     command: Option<Command>,
+    /// Seed for the moves of the AI, random if not set.
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Game record file to step through with the arrow keys.
+    #[clap(long)]
+    replay: Option<std::path::PathBuf>,
     #[clap(flatten)]
     geng: geng::CliArgs,
 }
@@ -15,14 +27,49 @@ struct Opts {
 #[derive(clap::Subcommand)]
 enum Command {
     Test,
+    /// Explain a single position.
+    Explain(cli::ExplainArgs),
+    /// Average SVERL-P over the states visited by a policy.
+    Aggregate(cli::AggregateArgs),
+    /// Find the smallest changes to a position that change the decision on it.
+    Counterfactual(cli::CounterfactualArgs),
+    /// Explain every reachable state and save the results for the GUI.
+    Precompute(cli::PrecomputeArgs),
+    /// Play games between two policies and report the results.
+    Tournament(cli::TournamentArgs),
+    /// Render an explanation to an SVG or PNG file.
+    Export(cli::ExportArgs),
+    /// Answer requests for explanations over HTTP.
+    Serve(cli::ServeArgs),
+    /// Explore positions interactively.
+    Repl(cli::ReplArgs),
+    /// Time the explanation engine on a fixed set of positions.
+    Bench(cli::BenchArgs),
 }
 
 #[derive(geng::asset::Load, Serialize, Deserialize)]
 #[load(serde = "toml")]
 struct Config {
+    /// Discount factor used by SVERL-P.
+    gamma: f64,
+    /// Depth limit of the minimax search, unlimited if not set.
+    minimax_depth: Option<usize>,
+    /// Parameters `alpha` and `beta` of Beta Shapley.
+    beta_shapley: (f64, f64),
+    /// Random observations the LIME surrogate is fitted on.
+    lime_samples: usize,
     palette: Palette,
 }
 
+impl Config {
+    /// Reads the config without the asset manager, for commands that run without a window.
+    fn read() -> Self {
+        let path = run_dir().join("assets").join("config.toml");
+        let text = std::fs::read_to_string(&path).expect("failed to read config");
+        toml::from_str(&text).expect("failed to parse config")
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct Palette {
     background: Rgba<f32>,
@@ -49,11 +96,11 @@ fn main() {
                 println!("shapley: {:?}", shapley);
                 println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
 
-                let sverl = tictactoe::Grid::new().sverl(false, 0.5, &mut policy);
+                let sverl = tictactoe::Grid::new().sverl(false, 0.5, &mut policy, None);
                 println!("sverl local: {:?}", sverl);
                 println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
 
-                let sverl = tictactoe::Grid::new().sverl(true, 0.5, &mut policy);
+                let sverl = tictactoe::Grid::new().sverl(true, 0.5, &mut policy, None);
                 println!("sverl global: {:?}", sverl);
                 println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
 
@@ -65,16 +112,52 @@ fn main() {
                 println!("shapley: {:?}", shapley);
                 println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
 
-                let sverl = tictactoe::Grid::new().sverl(false, 0.5, &mut policy);
+                let sverl = tictactoe::Grid::new().sverl(false, 0.5, &mut policy, None);
                 println!("sverl local: {:?}", sverl);
                 println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
 
-                let sverl = tictactoe::Grid::new().sverl(true, 0.5, &mut policy);
+                let sverl = tictactoe::Grid::new().sverl(true, 0.5, &mut policy, None);
                 println!("sverl global: {:?}", sverl);
                 println!("calc took {}ms", timer.tick().as_secs_f64() * 1000.0);
 
                 return;
             }
+            Command::Explain(args) => {
+                cli::explain(args);
+                return;
+            }
+            Command::Counterfactual(args) => {
+                cli::counterfactual(args);
+                return;
+            }
+            Command::Aggregate(args) => {
+                cli::aggregate(args);
+                return;
+            }
+            Command::Precompute(args) => {
+                cli::precompute(args);
+                return;
+            }
+            Command::Tournament(args) => {
+                cli::tournament(args);
+                return;
+            }
+            Command::Export(args) => {
+                cli::export(args);
+                return;
+            }
+            Command::Serve(args) => {
+                cli::serve(args);
+                return;
+            }
+            Command::Repl(args) => {
+                cli::repl(args);
+                return;
+            }
+            Command::Bench(args) => {
+                cli::bench(args);
+                return;
+            }
         }
     }
 
@@ -89,14 +172,39 @@ fn main() {
     let mut options = geng::ContextOptions::default();
     options.window.title = "XAI".into();
     options.with_cli(&opts.geng);
-    Geng::run_with(&options, |geng| async move {
+    let seed = opts.seed;
+    let replay = opts.replay;
+    Geng::run_with(&options, move |geng| async move {
         let manager = geng.asset_manager();
         let config: Config =
             geng::asset::Load::load(manager, &run_dir().join("assets").join("config.toml"), &())
                 .await
                 .expect("failed to load config");
 
-        let state = state::State::new(&geng, config);
+        let mut databases = Vec::new();
+        for policy in ["random", "minimax"] {
+            let path = run_dir()
+                .join("assets")
+                .join("database")
+                .join(format!("{}.json", policy));
+            match file::load_json::<tictactoe::Database>(&path).await {
+                Ok(database) => match database.check() {
+                    Ok(()) => databases.push(database),
+                    Err(err) => {
+                        log::warn!("ignoring precomputed explanations at {:?}: {}", path, err)
+                    }
+                },
+                Err(err) => log::warn!("no precomputed explanations at {:?}: {}", path, err),
+            }
+        }
+
+        let mut state = state::State::new(&geng, config, databases, seed);
+        if let Some(path) = replay {
+            let games: Vec<tictactoe::GameRecord> = file::load_json(&path)
+                .await
+                .expect("failed to load the games to replay");
+            state.load_games(games).expect("invalid games to replay");
+        }
         geng.run_state(state).await;
     });
 }
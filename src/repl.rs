@@ -0,0 +1,298 @@
+use crate::{
+    cli::{self, PolicyArg},
+    terminal,
+    tictactoe::{self, Coord, Grid, MinimaxCache, Semivalue},
+    Config,
+};
+
+use std::io::{BufRead, Write};
+
+use geng::prelude::*;
+
+const HELP: &str = "\
+board <board>          set up the board, e.g. `board x.o/.x./...`
+move <cell>            play in the cell, indexed in reading order from 0
+undo                   take back the last change to the board
+reset                  clear the board
+policy random|minimax  explain another policy
+opponent <policy>|none policy of the other player in SVERL-P rollouts, `none` for self-play
+depth <n>|none         depth limit of the minimax search
+gamma <gamma>          discount factor of SVERL-P
+weighting <weighting>  `shapley`, `banzhaf`, or `beta:<alpha>,<beta>`
+pi                     probabilities of the actions
+pi_c <cells>           probabilities when only the cells are observed, e.g. `pi_c 0,1,4`
+shapley                contributions of each cell to the probabilities
+sverl local|global     contributions of each cell to the expected return
+minimax                values of the actions for the current player
+show                   print the board and the settings
+quit";
+
+/// Interactive exploration of positions, see [HELP] for the commands.
+pub struct Repl {
+    board: Grid,
+    /// Previous boards, for `undo`.
+    history: Vec<Grid>,
+    policy: PolicyArg,
+    /// Policy of the other player in SVERL-P rollouts, `None` for self-play.
+    opponent: Option<PolicyArg>,
+    depth: Option<usize>,
+    gamma: f64,
+    weighting: Semivalue,
+    /// Used to color the values, `None` prints plain numbers.
+    config: Option<Config>,
+    /// Minimax evaluations of every depth limit.
+    minimax_cache: MinimaxCache,
+    opponent_cache: MinimaxCache,
+    /// Printed results by query, which include the board and the settings.
+    results: HashMap<String, Vec<String>>,
+}
+
+impl Repl {
+    pub fn new(
+        board: Grid,
+        policy: PolicyArg,
+        depth: Option<usize>,
+        gamma: f64,
+        color: bool,
+    ) -> Self {
+        Self {
+            board,
+            history: Vec::new(),
+            policy,
+            opponent: None,
+            depth,
+            gamma,
+            weighting: Semivalue::Shapley,
+            config: color.then(Config::read),
+            minimax_cache: MinimaxCache::new(),
+            opponent_cache: MinimaxCache::new(),
+            results: HashMap::new(),
+        }
+    }
+
+    /// Reads commands from the standard input until it is closed or `quit` is entered.
+    pub fn run(&mut self) {
+        println!("type `help` for the commands");
+        self.show();
+        let stdin = std::io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("> ");
+            std::io::stdout()
+                .flush()
+                .expect("failed to write the prompt");
+            let Some(line) = lines.next() else {
+                println!();
+                break;
+            };
+            let line = line.expect("failed to read the command");
+            let line = line.trim();
+            if line == "quit" || line == "exit" {
+                break;
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut timer = Timer::new();
+            match self.execute(line) {
+                Ok(()) => {
+                    let time = timer.tick().as_secs_f64();
+                    if time > 0.1 {
+                        println!("calc took {}ms", time * 1000.0);
+                    }
+                }
+                Err(err) => println!("error: {}", err),
+            }
+        }
+    }
+
+    fn execute(&mut self, line: &str) -> Result<(), String> {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        match command {
+            "help" => println!("{}", HELP),
+            "show" => self.show(),
+            "board" => {
+                let board = args.parse()?;
+                self.history.push(std::mem::replace(&mut self.board, board));
+                self.show_board();
+            }
+            "move" => {
+                let &[pos] = parse_cells(args)?.as_slice() else {
+                    return Err("expected a single cell".to_string());
+                };
+                let player = self.board.current_player().ok_or("the game is over")?;
+                if !self.board.check(pos) {
+                    return Err("the cell is occupied".to_string());
+                }
+                self.history.push(self.board.clone());
+                self.board.set(pos, player.into());
+                self.show_board();
+            }
+            "undo" => {
+                self.board = self.history.pop().ok_or("nothing to undo")?;
+                self.show_board();
+            }
+            "reset" => {
+                self.history
+                    .push(std::mem::replace(&mut self.board, Grid::new()));
+                self.show_board();
+            }
+            "policy" => {
+                self.policy = clap::ValueEnum::from_str(args, true)?;
+            }
+            "opponent" => {
+                self.opponent = match args {
+                    "none" => None,
+                    _ => Some(clap::ValueEnum::from_str(args, true)?),
+                };
+            }
+            "depth" => {
+                self.depth = match args {
+                    "none" => None,
+                    _ => Some(
+                        args.parse()
+                            .map_err(|_| format!("invalid depth {:?}", args))?,
+                    ),
+                };
+            }
+            "gamma" => {
+                self.gamma = cli::parse_gamma(args)?;
+            }
+            "weighting" => {
+                self.weighting = args.parse()?;
+            }
+            "pi" | "pi_c" | "shapley" | "sverl" | "minimax" => {
+                let key = format!(
+                    "{} {} {} {:?} {:?} {:?} {} {}",
+                    command,
+                    args,
+                    self.board,
+                    self.policy,
+                    self.opponent,
+                    self.depth,
+                    self.gamma,
+                    self.weighting
+                );
+                let lines = match self.results.get(&key) {
+                    Some(lines) => lines.clone(),
+                    None => {
+                        let lines = self.query(command, args)?;
+                        self.results.insert(key, lines.clone());
+                        lines
+                    }
+                };
+                for line in lines {
+                    println!("{}", line);
+                }
+            }
+            _ => return Err(format!("unknown command {:?}, try `help`", command)),
+        }
+        Ok(())
+    }
+
+    /// Explains the board, returning the lines to print.
+    fn query(&mut self, command: &str, args: &str) -> Result<Vec<String>, String> {
+        let palette = self.config.as_ref().map(|config| &config.palette);
+        let board = &self.board;
+        let cache = &mut self.minimax_cache;
+        if command == "minimax" {
+            let player = board.current_player().ok_or("the game is over")?;
+            let values = tictactoe::minimax(board, cache, player, self.depth);
+            let value = tictactoe::minimax_value(board, cache, player, self.depth);
+            let mut lines = vec![format!("{:?} to play, value {:+.2}", player, value)];
+            lines.extend(terminal::board_lines(palette, board, &values));
+            return Ok(lines);
+        }
+
+        let parameters =
+            self.policy
+                .parameters(self.depth, self.opponent, self.weighting, self.gamma);
+        let mut policy = self.policy.build(self.depth, cache);
+        let lines = match command {
+            "pi" => terminal::board_lines(palette, board, &policy(board)),
+            "pi_c" => {
+                let observed = if args.is_empty() {
+                    Vec::new()
+                } else {
+                    parse_cells(args)?
+                };
+                let hidden: Vec<_> = board
+                    .positions()
+                    .filter(|pos| !observed.contains(pos))
+                    .collect();
+                let values = board.partial_observation(&hidden).value(&mut policy);
+                terminal::board_lines(palette, board, &values)
+            }
+            "shapley" => {
+                let explanation = board.explain_policy(&parameters, &mut policy);
+                let mut lines =
+                    terminal::small_multiples_lines(palette, board, &explanation.values);
+                lines.push(cli::efficiency(&explanation));
+                lines
+            }
+            "sverl" => {
+                let global = match args {
+                    "local" | "" => false,
+                    "global" => true,
+                    _ => return Err("expected `sverl local` or `sverl global`".to_string()),
+                };
+                let mut opponent = self
+                    .opponent
+                    .map(|opponent| opponent.build(self.depth, &mut self.opponent_cache));
+                let explanation =
+                    board.explain_sverl(global, &parameters, &mut policy, opponent.as_mut());
+                let mut lines = terminal::board_lines(palette, board, &explanation.values);
+                lines.push(cli::efficiency(&explanation));
+                lines
+            }
+            _ => unreachable!(),
+        };
+        Ok(lines)
+    }
+
+    fn show(&self) {
+        self.show_board();
+        println!(
+            "policy {}, opponent {}, depth {}, gamma {}, weighting {}",
+            self.policy.name(),
+            self.opponent
+                .map_or("none".to_string(), |opponent| opponent.name()),
+            self.depth
+                .map_or("none".to_string(), |depth| depth.to_string()),
+            self.gamma,
+            self.weighting
+        );
+    }
+
+    fn show_board(&self) {
+        for row in self.board.to_string().split('/') {
+            println!("  {}", row);
+        }
+        match self.board.current_player() {
+            Some(player) => println!("{:?} to play", player),
+            None => match self.board.winner() {
+                Some(winner) => println!("{:?} won", winner),
+                None => println!("draw"),
+            },
+        }
+    }
+}
+
+/// Parses comma separated cell indices in reading order.
+fn parse_cells(s: &str) -> Result<Vec<vec2<Coord>>, String> {
+    let positions: Vec<_> = Grid::new().reading_order().collect();
+    s.split(',')
+        .map(|index| {
+            let index: usize = index
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid cell index {:?}", index))?;
+            positions
+                .get(index)
+                .copied()
+                .ok_or_else(|| format!("cell index {} is too large", index))
+        })
+        .collect()
+}
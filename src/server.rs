@@ -0,0 +1,184 @@
+//! Explanations over HTTP: every endpoint takes a [Request] as JSON in a `POST`.
+//! Boards are in the text format of the command line, grids are rows from the bottom one.
+
+use crate::{
+    cli::PolicyArg,
+    tictactoe::{self, board_text, Grid, MinimaxCache, Player, Semivalue},
+};
+
+use geng::prelude::*;
+
+const ENDPOINTS: [&str; 4] = ["/policy", "/minimax", "/shapley", "/sverl"];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    #[serde(with = "board_text")]
+    board: Grid,
+    #[serde(default = "default_policy")]
+    policy: PolicyArg,
+    /// Depth limit of the minimax search, unlimited if not set.
+    #[serde(default)]
+    depth: Option<usize>,
+    /// Used by `/shapley` and `/sverl`.
+    #[serde(default = "default_weighting")]
+    weighting: Semivalue,
+    /// Used by `/sverl`.
+    #[serde(default = "default_gamma")]
+    gamma: f64,
+    /// Used by `/sverl`.
+    #[serde(default)]
+    global: bool,
+    /// Policy of the other player in the rollouts of `/sverl`, the explained policy if not set.
+    #[serde(default)]
+    opponent: Option<PolicyArg>,
+}
+
+fn default_policy() -> PolicyArg {
+    PolicyArg::Minimax
+}
+
+fn default_weighting() -> Semivalue {
+    Semivalue::Shapley
+}
+
+fn default_gamma() -> f64 {
+    0.9
+}
+
+#[derive(Serialize)]
+struct PolicyResponse {
+    probabilities: Grid<f64>,
+}
+
+#[derive(Serialize)]
+struct MinimaxResponse {
+    player: Player,
+    /// Value of each action for the player, 0 for occupied cells.
+    values: Grid<f64>,
+    /// Value of the best action.
+    value: f64,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Default)]
+pub struct Server {
+    /// Minimax evaluations of every depth limit.
+    minimax_cache: MinimaxCache,
+    opponent_cache: MinimaxCache,
+    /// Response bodies by endpoint and request.
+    responses: HashMap<String, String>,
+}
+
+impl Server {
+    /// Serves requests one at a time until the process is stopped.
+    pub fn run(&mut self, address: &str) {
+        let server = tiny_http::Server::http(address).expect("failed to start the server");
+        println!("listening on http://{}", address);
+        let content_type: tiny_http::Header = "Content-Type: application/json".parse().unwrap();
+
+        for mut request in server.incoming_requests() {
+            let mut timer = Timer::new();
+            let mut body = String::new();
+            let (status, body) = match request.as_reader().read_to_string(&mut body) {
+                Err(err) => (400, error(format!("failed to read the request: {}", err))),
+                Ok(_) if *request.method() != tiny_http::Method::Post => (
+                    405,
+                    error(format!("expected a POST to one of {:?}", ENDPOINTS)),
+                ),
+                Ok(_) => match self.respond(request.url(), &body) {
+                    Ok(body) => (200, body),
+                    Err((status, message)) => (status, error(message)),
+                },
+            };
+            println!(
+                "{} {} {} in {}ms",
+                request.method(),
+                request.url(),
+                status,
+                timer.tick().as_secs_f64() * 1000.0
+            );
+
+            let response = tiny_http::Response::from_string(body)
+                .with_status_code(status)
+                .with_header(content_type.clone());
+            if let Err(err) = request.respond(response) {
+                eprintln!("failed to respond: {}", err);
+            }
+        }
+    }
+
+    /// Returns the response body, or the status and message of the error.
+    fn respond(&mut self, endpoint: &str, body: &str) -> Result<String, (u16, String)> {
+        if !ENDPOINTS.contains(&endpoint) {
+            return Err((
+                404,
+                format!("unknown endpoint, expected one of {:?}", ENDPOINTS),
+            ));
+        }
+        let request: Request = serde_json::from_str(body).map_err(|err| (400, err.to_string()))?;
+
+        let key = format!("{} {:?}", endpoint, request);
+        if let Some(cached) = self.responses.get(&key) {
+            return Ok(cached.clone());
+        }
+
+        let board = &request.board;
+        let parameters = request.policy.parameters(
+            request.depth,
+            request.opponent,
+            request.weighting,
+            request.gamma,
+        );
+        let cache = &mut self.minimax_cache;
+        let body = match endpoint {
+            "/policy" => {
+                let mut policy = request.policy.build(request.depth, cache);
+                json(&PolicyResponse {
+                    probabilities: policy(board),
+                })
+            }
+            "/minimax" => {
+                let player = board
+                    .current_player()
+                    .ok_or((400, "the game is over".to_string()))?;
+                json(&MinimaxResponse {
+                    player,
+                    values: tictactoe::minimax(board, cache, player, request.depth),
+                    value: tictactoe::minimax_value(board, cache, player, request.depth),
+                })
+            }
+            "/shapley" => {
+                let mut policy = request.policy.build(request.depth, cache);
+                json(&board.explain_policy(&parameters, &mut policy))
+            }
+            "/sverl" => {
+                let mut policy = request.policy.build(request.depth, cache);
+                let mut opponent = request
+                    .opponent
+                    .map(|opponent| opponent.build(request.depth, &mut self.opponent_cache));
+                json(&board.explain_sverl(
+                    request.global,
+                    &parameters,
+                    &mut policy,
+                    opponent.as_mut(),
+                ))
+            }
+            _ => unreachable!(),
+        };
+        self.responses.insert(key, body.clone());
+        Ok(body)
+    }
+}
+
+fn json(response: &impl Serialize) -> String {
+    serde_json::to_string(response).expect("failed to serialize the response")
+}
+
+fn error(message: String) -> String {
+    json(&ErrorResponse { error: message })
+}
@@ -15,41 +15,297 @@ pub struct State {
     framebuffer_size: vec2<usize>,
     ui: Ui,
     camera: Camera2d,
-    policy: Policy,
+    player_x: Side,
+    player_o: Side,
     method: Method,
     sverl_global: bool,
+    comparison: Option<Comparison>,
+    autoplay: bool,
+    /// Delay between AI moves in seconds.
+    autoplay_delay: f64,
+    autoplay_timer: f64,
 
     touch: TouchController,
 
     model: Grid,
-    minimax_cache: BTreeMap<Grid, Grid<f64>>,
-    shapley_values: Option<Grid<Grid<f64>>>,
-    sverl_values_local: Option<Grid<f64>>,
-    sverl_values_global: Option<Grid<f64>>,
+    /// The board the current game started from.
+    opening: Grid,
+    history: Vec<Action>,
+    /// Explanations shown before the moves, saved with the game.
+    explanations: Vec<PlyExplanation>,
+    replay: Option<Replay>,
+    minimax_cache: MinimaxCache,
+    opponent_minimax_cache: MinimaxCache,
+    /// Used for the moves of the AI, seeded anew for every game.
+    rng: StdRng,
+    seed: u64,
+    values: HashMap<View, Values>,
+    /// Precomputed explanations, used when they match the current settings.
+    databases: Vec<Database>,
+    /// Explanations over all visited states, kept across moves.
+    aggregates: HashMap<View, AggregateStatus>,
+    /// Cells hidden from the policy in the coalition view.
+    hidden: Vec<vec2<Coord>>,
+    /// Whether to highlight the smallest changes to the board that change the top action.
+    show_counterfactuals: bool,
+    /// Counterfactuals of the model by explained policy, the first one is highlighted.
+    counterfactuals: HashMap<Policy, Vec<Counterfactual>>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Policy {
     Random,
     Minimax,
 }
 
+impl Policy {
+    fn next(self) -> Self {
+        match self {
+            Policy::Random => Policy::Minimax,
+            Policy::Minimax => Policy::Random,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Policy::Random => "Random",
+            Policy::Minimax => "Minimax",
+        }
+    }
+
+    /// The name used on the command line and in precomputed databases.
+    fn id(self) -> &'static str {
+        match self {
+            Policy::Random => "random",
+            Policy::Minimax => "minimax",
+        }
+    }
+
+    fn build(
+        self,
+        depth: Option<usize>,
+        minimax_cache: &mut MinimaxCache,
+    ) -> crate::tictactoe::Policy<'_> {
+        match self {
+            Policy::Random => policy_random(),
+            Policy::Minimax => policy_minimax_cached(depth, minimax_cache),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Controller {
+    Human,
+    Ai,
+}
+
+/// Loaded games being stepped through.
+struct Replay {
+    games: Vec<GameRecord>,
+    game: usize,
+    ply: usize,
+}
+
 #[derive(Debug, Clone, Copy)]
+struct Side {
+    controller: Controller,
+    /// Makes the moves of the AI, and is explained on this side's turn.
+    policy: Policy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Method {
     Shapley,
-    Sverl { global: bool },
+    Sverl {
+        global: bool,
+    },
+    /// SVERL-P averaged over the states visited by the policy.
+    Aggregate {
+        global: bool,
+    },
+    /// Contributions to the state value of the policy.
+    StateValue,
+    /// Banzhaf or Beta Shapley values of the policy.
+    Semivalue {
+        beta: bool,
+    },
+    /// The policy with the cells hidden by the user.
+    Coalition,
+    /// Pairwise Shapley interactions of the policy or of SVERL-P local.
+    Interaction {
+        sverl: bool,
+    },
+    /// Occlusion of single cells or a LIME surrogate.
+    Baseline {
+        lime: bool,
+    },
+}
+
+impl Method {
+    fn name(self) -> &'static str {
+        match self {
+            Method::Shapley => "Shapley",
+            Method::Sverl { global: false } => "SVERL-P local",
+            Method::Sverl { global: true } => "SVERL-P global",
+            Method::Aggregate { global: false } => "Average SVERL-P local",
+            Method::Aggregate { global: true } => "Average SVERL-P global",
+            Method::StateValue => "State value",
+            Method::Semivalue { beta: false } => "Banzhaf",
+            Method::Semivalue { beta: true } => "Beta Shapley",
+            Method::Coalition => "Coalition",
+            Method::Interaction { sverl: false } => "Policy interaction",
+            Method::Interaction { sverl: true } => "SVERL-P interaction",
+            Method::Baseline { lime: false } => "Occlusion",
+            Method::Baseline { lime: true } => "LIME",
+        }
+    }
+
+    fn uses_gamma(self) -> bool {
+        matches!(
+            self,
+            Method::Sverl { .. }
+                | Method::Aggregate { .. }
+                | Method::StateValue
+                | Method::Interaction { sverl: true }
+        )
+    }
+}
+
+/// A single explanation shown on a board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct View {
+    policy: Policy,
+    /// Policy of the other player in SVERL-P rollouts, `None` for self-play.
+    opponent: Option<Policy>,
+    method: Method,
+}
+
+impl View {
+    /// Leaves out the opponent if it plays like the policy or the method does not roll out.
+    fn new(policy: Policy, opponent: Policy, method: Method) -> Self {
+        let rollouts = matches!(
+            method,
+            Method::Sverl { .. } | Method::Interaction { sverl: true } | Method::Aggregate { .. }
+        );
+        Self {
+            policy,
+            opponent: (rollouts && opponent != policy).then_some(opponent),
+            method,
+        }
+    }
+
+    fn name(self) -> String {
+        match self.opponent {
+            Some(opponent) => format!(
+                "{} {} vs {}",
+                self.policy.name(),
+                self.method.name(),
+                opponent.name()
+            ),
+            None => format!("{} {}", self.policy.name(), self.method.name()),
+        }
+    }
+}
+
+enum Values {
+    Shapley(Box<Explanation<Grid<Grid<f64>>>>),
+    Sverl(Box<Explanation<Grid<f64>>>),
+    /// The policy under partial observation, with the one under full observation.
+    Coalition(Box<Explanation<Grid<f64>>>),
+    Interaction {
+        explanation: Box<Explanation<Grid<Grid<Grid<f64>>>>>,
+        /// Interactions of each pair of cells, summed over the actions.
+        pairs: Box<Grid<Grid<f64>>>,
+    },
+}
+
+enum AggregateStatus {
+    Running(AggregateProgress),
+    Done(AggregateSverl),
+}
+
+/// What the main view is compared against in the split view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    /// Another method, same policy.
+    Method,
+    /// Same method, the other policy.
+    Policy,
+}
+
+impl Comparison {
+    fn other(self, view: View, opponent: Policy) -> View {
+        match self {
+            Comparison::Method => View::new(
+                view.policy,
+                opponent,
+                match view.method {
+                    Method::Shapley => Method::Sverl { global: false },
+                    Method::Sverl { global } => Method::Sverl { global: !global },
+                    Method::Aggregate { global } => Method::Aggregate { global: !global },
+                    Method::StateValue => Method::Sverl { global: false },
+                    Method::Semivalue { .. } => Method::Shapley,
+                    Method::Coalition => Method::Shapley,
+                    Method::Baseline { .. } => Method::Shapley,
+                    Method::Interaction { sverl } => Method::Interaction { sverl: !sverl },
+                },
+            ),
+            Comparison::Policy => View::new(view.policy.next(), opponent, view.method),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Panel {
+    View(View),
+    /// The full observation policy of a coalition view.
+    FullPolicy(View),
+    /// The first view minus the second one.
+    Difference(View, View),
+}
+
+/// Largest depth limit before the search becomes unlimited.
+const MAX_MINIMAX_DEPTH: usize = 8;
+
+/// Time spent on aggregate explanations per frame, in seconds.
+const AGGREGATE_FRAME_BUDGET: f64 = 0.02;
+
+/// Horizontal distance between the boards in the split view.
+const BOARD_SPACING: f32 = 4.0;
+
+fn board_offset(index: usize) -> vec2<f32> {
+    vec2(index as f32 * BOARD_SPACING, 0.0)
 }
 
 pub struct Ui {
     font_size: f32,
     cursor_pos: vec2<f32>,
-    policy_random: Aabb2<f32>,
-    policy_minimax: Aabb2<f32>,
+    player_x_controller: Aabb2<f32>,
+    player_x_policy: Aabb2<f32>,
+    player_o_controller: Aabb2<f32>,
+    player_o_policy: Aabb2<f32>,
     method_shapley: Aabb2<f32>,
     method_sverl: Aabb2<f32>,
     method_sverl_global: Aabb2<f32>,
+    method_sverl_average: Aabb2<f32>,
+    method_semivalue: Aabb2<f32>,
+    method_state_value: Aabb2<f32>,
+    method_semivalue_beta: Aabb2<f32>,
+    method_coalition: Aabb2<f32>,
+    method_interaction: Aabb2<f32>,
+    method_interaction_sverl: Aabb2<f32>,
     board_reset: Aabb2<f32>,
     board_policy_turn: Aabb2<f32>,
+    compare: Aabb2<f32>,
+    gamma: Aabb2<f32>,
+    gamma_decrease: Aabb2<f32>,
+    gamma_increase: Aabb2<f32>,
+    depth: Aabb2<f32>,
+    depth_decrease: Aabb2<f32>,
+    depth_increase: Aabb2<f32>,
+    autoplay: Aabb2<f32>,
+    autoplay_slower: Aabb2<f32>,
+    autoplay_faster: Aabb2<f32>,
 }
 
 impl Ui {
@@ -57,13 +313,32 @@ impl Ui {
         Self {
             font_size: 1.0,
             cursor_pos: vec2::ZERO,
-            policy_random: Aabb2::ZERO,
-            policy_minimax: Aabb2::ZERO,
+            player_x_controller: Aabb2::ZERO,
+            player_x_policy: Aabb2::ZERO,
+            player_o_controller: Aabb2::ZERO,
+            player_o_policy: Aabb2::ZERO,
             method_shapley: Aabb2::ZERO,
             method_sverl: Aabb2::ZERO,
             method_sverl_global: Aabb2::ZERO,
+            method_sverl_average: Aabb2::ZERO,
+            method_semivalue: Aabb2::ZERO,
+            method_state_value: Aabb2::ZERO,
+            method_semivalue_beta: Aabb2::ZERO,
+            method_coalition: Aabb2::ZERO,
+            method_interaction: Aabb2::ZERO,
+            method_interaction_sverl: Aabb2::ZERO,
             board_reset: Aabb2::ZERO,
             board_policy_turn: Aabb2::ZERO,
+            compare: Aabb2::ZERO,
+            gamma: Aabb2::ZERO,
+            gamma_decrease: Aabb2::ZERO,
+            gamma_increase: Aabb2::ZERO,
+            depth: Aabb2::ZERO,
+            depth_decrease: Aabb2::ZERO,
+            depth_increase: Aabb2::ZERO,
+            autoplay: Aabb2::ZERO,
+            autoplay_slower: Aabb2::ZERO,
+            autoplay_faster: Aabb2::ZERO,
         }
     }
 
@@ -77,49 +352,87 @@ impl Ui {
 
         let pos = framebuffer_size
             * if portrait {
-                vec2(0.1, 0.9)
+                vec2(0.05, 0.92)
             } else {
-                vec2(0.1, 0.7)
+                vec2(0.1, 0.85)
             };
         let offset_x = vec2(button.width() + font_size * 0.5, 0.0);
         let offset_y = vec2(0.0, button.height() + font_size * 0.5);
-        let offset = if portrait { -offset_x } else { offset_y };
+        let at = |column: f32, row: f32| button.translate(pos + offset_x * column - offset_y * row);
+        // Rows are skipped where the small buttons go
         if portrait {
-            self.policy_random = button.translate(pos);
-            self.policy_minimax = button.translate(pos - offset_y);
-            self.method_shapley = button.translate(pos - offset * 1.0);
-            self.method_sverl = button.translate(pos - offset * 1.0 - offset_y);
-            self.board_reset = button.translate(pos - offset * 2.0);
-            self.board_policy_turn = button.translate(pos - offset * 2.0 - offset_y);
+            self.player_x_controller = at(0.0, 0.0);
+            self.player_x_policy = at(1.0, 0.0);
+            self.player_o_controller = at(2.0, 0.0);
+            self.player_o_policy = at(3.0, 0.0);
+            self.method_shapley = at(0.0, 1.0);
+            self.method_sverl = at(1.0, 1.0);
+            self.method_coalition = at(2.0, 1.0);
+            self.method_interaction = at(3.0, 1.0);
+            self.autoplay = at(0.0, 3.0);
+            self.compare = at(1.0, 3.0);
+            self.board_reset = at(2.0, 3.0);
+            self.board_policy_turn = at(3.0, 3.0);
+            self.gamma = at(0.0, 5.0);
+            self.method_state_value = at(1.0, 5.0);
+            self.depth = at(2.0, 5.0);
+            self.method_semivalue = at(3.0, 5.0);
         } else {
-            self.policy_random = button.translate(pos);
-            self.policy_minimax = button.translate(pos - offset);
-            self.method_shapley = button.translate(pos - offset * 3.0);
-            self.method_sverl = button.translate(pos - offset * 4.0);
-            self.board_reset = button.translate(pos - offset * 6.0);
-            self.board_policy_turn = button.translate(pos - offset * 7.0);
+            self.player_x_controller = at(0.0, 0.0);
+            self.player_x_policy = at(1.0, 0.0);
+            self.player_o_controller = at(0.0, 1.0);
+            self.player_o_policy = at(1.0, 1.0);
+            self.method_shapley = at(0.0, 2.0);
+            self.compare = at(1.0, 2.0);
+            self.method_sverl = at(0.0, 3.0);
+            self.method_coalition = at(0.0, 4.0);
+            self.method_state_value = at(1.0, 4.0);
+            self.method_interaction = at(0.0, 5.0);
+            self.method_semivalue = at(0.0, 6.0);
+            self.board_reset = at(0.0, 7.0);
+            self.board_policy_turn = at(1.0, 7.0);
+            self.autoplay = at(0.0, 8.0);
+            self.gamma = at(0.0, 9.0);
+            self.depth = at(0.0, 10.0);
         }
 
         let smol_button = vec2(3.5, 1.5) * font_size;
-        let pos = geng_utils::layout::align_aabb(
-            smol_button,
-            self.method_sverl,
-            if portrait {
-                vec2(0.5, 0.0)
+        let below_or_right = |target: Aabb2<f32>| {
+            let pos = geng_utils::layout::align_aabb(
+                smol_button,
+                target,
+                if portrait {
+                    vec2(0.5, 0.0)
+                } else {
+                    vec2(1.0, 0.5)
+                },
+            );
+            pos.translate(if portrait {
+                vec2(0.0, -font_size - smol_button.y)
             } else {
-                vec2(1.0, 0.5)
-            },
-        );
-        self.method_sverl_global = pos.translate(if portrait {
-            vec2(0.0, -font_size - smol_button.y)
-        } else {
-            vec2(font_size + smol_button.x, 0.0)
-        });
+                vec2(font_size + smol_button.x, 0.0)
+            })
+        };
+        self.method_interaction_sverl = below_or_right(self.method_interaction);
+        self.method_semivalue_beta = below_or_right(self.method_semivalue);
+
+        let stepper = |target: Aabb2<f32>| {
+            let decrease = below_or_right(target);
+            let increase = decrease.translate(vec2(smol_button.x + font_size * 0.5, 0.0));
+            (decrease, increase)
+        };
+        (self.method_sverl_global, self.method_sverl_average) = stepper(self.method_sverl);
+        (self.gamma_decrease, self.gamma_increase) = stepper(self.gamma);
+        (self.depth_decrease, self.depth_increase) = stepper(self.depth);
+        (self.autoplay_slower, self.autoplay_faster) = stepper(self.autoplay);
     }
 }
 
 impl State {
-    pub fn new(geng: &Geng, config: Config) -> State {
+    pub fn new(geng: &Geng, config: Config, databases: Vec<Database>, seed: Option<u64>) -> State {
+        let (seed, rng) = seeded_rng(seed);
+        log::info!("random seed: {}", seed);
+
         let mut state = State {
             geng: geng.clone(),
             config,
@@ -130,26 +443,46 @@ impl State {
                 rotation: Angle::ZERO,
                 fov: 10.0,
             },
-            policy: Policy::Minimax,
+            player_x: Side {
+                controller: Controller::Human,
+                policy: Policy::Minimax,
+            },
+            player_o: Side {
+                controller: Controller::Human,
+                policy: Policy::Minimax,
+            },
             method: Method::Sverl { global: false },
             sverl_global: false,
+            comparison: None,
+            autoplay: true,
+            autoplay_delay: 0.5,
+            autoplay_timer: 0.0,
 
             touch: TouchController::new(),
 
             model: Grid::new(),
+            opening: Grid::new(),
+            history: Vec::new(),
+            explanations: Vec::new(),
+            replay: None,
             minimax_cache: BTreeMap::new(),
-            shapley_values: None,
-            sverl_values_local: None,
-            sverl_values_global: None,
+            opponent_minimax_cache: BTreeMap::new(),
+            rng,
+            seed,
+            values: HashMap::new(),
+            databases,
+            aggregates: HashMap::new(),
+            hidden: Vec::new(),
+            show_counterfactuals: false,
+            counterfactuals: HashMap::new(),
         };
         state.update_values(true);
         state
     }
 
-    fn draw_x(&self, pos: vec2<usize>, transparency: f32, framebuffer: &mut ugli::Framebuffer) {
+    fn draw_x(&self, pos: vec2<f32>, transparency: f32, framebuffer: &mut ugli::Framebuffer) {
         let ratio = 0.7;
-        let aabb =
-            Aabb2::point(pos.as_f32() + vec2(0.5, 0.5)).extend_symmetric(vec2(ratio, ratio) / 2.0);
+        let aabb = Aabb2::point(pos + vec2(0.5, 0.5)).extend_symmetric(vec2(ratio, ratio) / 2.0);
 
         let mut color = self.config.palette.grid;
         color.a *= transparency;
@@ -165,10 +498,9 @@ impl State {
         );
     }
 
-    fn draw_o(&self, pos: vec2<usize>, transparency: f32, framebuffer: &mut ugli::Framebuffer) {
+    fn draw_o(&self, pos: vec2<f32>, transparency: f32, framebuffer: &mut ugli::Framebuffer) {
         let ratio = 0.9;
-        let aabb =
-            Aabb2::point(pos.as_f32() + vec2(0.5, 0.5)).extend_symmetric(vec2(ratio, ratio) / 2.0);
+        let aabb = Aabb2::point(pos + vec2(0.5, 0.5)).extend_symmetric(vec2(ratio, ratio) / 2.0);
 
         let mut color = self.config.palette.grid;
         color.a *= transparency;
@@ -186,76 +518,394 @@ impl State {
 
     fn update_values(&mut self, reset: bool) {
         if reset {
-            self.shapley_values = None;
-            self.sverl_values_local = None;
-            self.sverl_values_global = None;
+            self.values.clear();
+            self.counterfactuals.clear();
         }
 
-        log::debug!("updating shapley and sverl values");
-        let mut policy = match self.policy {
-            Policy::Random => policy_random(),
-            Policy::Minimax => policy_minimax_cached(None, &mut self.minimax_cache),
+        for panel in self.panels() {
+            if let Panel::View(view) = panel {
+                self.update_view(view);
+            }
+        }
+        self.update_counterfactuals();
+    }
+
+    fn update_counterfactuals(&mut self) {
+        let policy = self.explained_policy();
+        if !self.show_counterfactuals
+            || self.model.current_player().is_none()
+            || self.counterfactuals.contains_key(&policy)
+        {
+            return;
+        }
+        let mut built = policy.build(self.config.minimax_depth, &mut self.minimax_cache);
+        let counterfactuals = self.model.top_action_counterfactuals(&mut built);
+        self.counterfactuals.insert(policy, counterfactuals);
+    }
+
+    fn update_view(&mut self, view: View) {
+        if let Method::Aggregate { global } = view.method {
+            // Independent of the board, explained over several frames in `update_aggregates`
+            if !self.aggregates.contains_key(&view) {
+                log::debug!("starting {}", view.name());
+                let mut policy = view
+                    .policy
+                    .build(self.config.minimax_depth, &mut self.minimax_cache);
+                let mut opponent = view.opponent.map(|opponent| {
+                    opponent.build(self.config.minimax_depth, &mut self.opponent_minimax_cache)
+                });
+                let progress = AggregateProgress::new(
+                    global,
+                    self.config.gamma,
+                    &mut policy,
+                    opponent.as_mut(),
+                );
+                self.aggregates
+                    .insert(view, AggregateStatus::Running(progress));
+            }
+            return;
+        }
+
+        if self.values.contains_key(&view) {
+            log::debug!("{} values cached", view.name());
+            return;
+        }
+
+        // Databases are computed in self-play
+        let precomputed = self
+            .database_entry(view.policy)
+            .filter(|_| view.opponent.is_none())
+            .and_then(|entry| match view.method {
+                Method::Shapley => Some(Values::Shapley(Box::new(entry.shapley.clone()))),
+                Method::Sverl { global: false } => {
+                    Some(Values::Sverl(Box::new(entry.sverl_local.clone())))
+                }
+                Method::Sverl { global: true } => {
+                    Some(Values::Sverl(Box::new(entry.sverl_global.clone())))
+                }
+                _ => None,
+            });
+        if let Some(values) = precomputed {
+            log::debug!("{} values precomputed", view.name());
+            self.values.insert(view, values);
+            return;
+        }
+
+        log::debug!("updating {} values", view.name());
+        let weighting = match view.method {
+            Method::Semivalue { beta: true } => {
+                let (alpha, beta) = self.config.beta_shapley;
+                Semivalue::BetaShapley { alpha, beta }
+            }
+            Method::Semivalue { beta: false } => Semivalue::Banzhaf,
+            _ => Semivalue::Shapley,
+        };
+        let minimax = view.policy == Policy::Minimax || view.opponent == Some(Policy::Minimax);
+        let parameters = ExplanationParameters {
+            policy: view.policy.id().to_string(),
+            minimax_depth: if minimax {
+                self.config.minimax_depth
+            } else {
+                None
+            },
+            opponent: view.opponent.map(|opponent| opponent.id().to_string()),
+            weighting,
+            gamma: self.config.gamma,
+            lime: None,
+            partition: None,
+            hidden: None,
         };
+        let mut policy = view
+            .policy
+            .build(self.config.minimax_depth, &mut self.minimax_cache);
 
         let mut timer = Timer::new();
-        match self.method {
-            Method::Shapley => {
-                if self.shapley_values.is_some() {
-                    log::debug!("shapley values cached");
-                    return;
-                }
-                self.shapley_values = Some(self.model.shapley(&mut policy));
-                log::debug!(
-                    "updated shapley values in {:.3}s",
-                    timer.tick().as_secs_f64()
-                );
+        let values = match view.method {
+            Method::Shapley | Method::Semivalue { .. } => Values::Shapley(Box::new(
+                self.model.explain_policy(&parameters, &mut policy),
+            )),
+            Method::Baseline { lime: false } => Values::Shapley(Box::new(
+                self.model.explain_occlusion(&parameters, &mut policy),
+            )),
+            Method::Baseline { lime: true } => {
+                // Seeded with the game, so that the values can be reproduced from the log
+                let sampling = LimeSampling {
+                    samples: self.config.lime_samples,
+                    seed: self.seed,
+                };
+                Values::Shapley(Box::new(self.model.explain_lime(
+                    &parameters,
+                    sampling,
+                    &mut policy,
+                )))
             }
+            Method::StateValue => Values::Sverl(Box::new(
+                self.model.explain_state_value(&parameters, &mut policy),
+            )),
             Method::Sverl { global } => {
-                if global {
-                    if self.sverl_values_global.is_some() {
-                        log::debug!("sverl global values cached");
-                        return;
-                    }
-                    self.sverl_values_global = Some(self.model.sverl(true, 0.9, &mut policy));
-                    log::debug!(
-                        "updated sverl global values in {:.3}s",
-                        timer.tick().as_secs_f64()
-                    );
-                } else {
-                    if self.sverl_values_local.is_some() {
-                        log::debug!("sverl local values cached");
-                        return;
-                    }
-                    self.sverl_values_local = Some(self.model.sverl(false, 0.9, &mut policy));
-                    log::debug!(
-                        "updated sverl local values in {:.3}s",
-                        timer.tick().as_secs_f64()
-                    );
+                let mut opponent = view.opponent.map(|opponent| {
+                    opponent.build(self.config.minimax_depth, &mut self.opponent_minimax_cache)
+                });
+                Values::Sverl(Box::new(self.model.explain_sverl(
+                    global,
+                    &parameters,
+                    &mut policy,
+                    opponent.as_mut(),
+                )))
+            }
+            Method::Coalition => Values::Coalition(Box::new(self.model.explain_coalition(
+                &parameters,
+                &self.hidden,
+                &mut policy,
+            ))),
+            Method::Interaction { sverl: true } => {
+                let mut opponent = view.opponent.map(|opponent| {
+                    opponent.build(self.config.minimax_depth, &mut self.opponent_minimax_cache)
+                });
+                let explanation = self.model.explain_sverl_interaction(
+                    &parameters,
+                    &mut policy,
+                    opponent.as_mut(),
+                );
+                let values = &explanation.values;
+                let pairs = Grid::from_fn(|i| {
+                    Grid::from_fn(|j| values.get(i).unwrap().get(j).unwrap().sum())
+                });
+                Values::Interaction {
+                    explanation: Box::new(explanation),
+                    pairs: Box::new(pairs),
+                }
+            }
+            Method::Interaction { sverl: false } => {
+                // Joint effect on the probability of the actions the policy takes
+                let weights = policy(&self.model);
+                let explanation = self.model.explain_interaction(&parameters, &mut policy);
+                let values = &explanation.values;
+                let pairs = Grid::from_fn(|i| {
+                    Grid::from_fn(|j| {
+                        let mut value = values.get(i).unwrap().get(j).unwrap().clone();
+                        value *= weights.clone();
+                        value.sum()
+                    })
+                });
+                Values::Interaction {
+                    explanation: Box::new(explanation),
+                    pairs: Box::new(pairs),
+                }
+            }
+            Method::Aggregate { .. } => unreachable!("aggregates are handled by update_aggregates"),
+        };
+        log::debug!(
+            "updated {} values in {:.3}s",
+            view.name(),
+            timer.tick().as_secs_f64()
+        );
+        self.values.insert(view, values);
+    }
+
+    /// The precomputed explanations of the board, if computed with the current settings.
+    fn database_entry(&self, policy: Policy) -> Option<&DatabaseEntry> {
+        self.databases
+            .iter()
+            .find(|database| {
+                database.policy == policy.id()
+                    && (database.gamma - self.config.gamma).abs() < 1e-9
+                    && (policy != Policy::Minimax
+                        || database.minimax_depth == self.config.minimax_depth)
+            })
+            .and_then(|database| database.get(&self.model))
+    }
+
+    /// Explains more states of the aggregates being shown, for a limited time.
+    fn update_aggregates(&mut self) {
+        let timer = Timer::new();
+        for panel in self.panels() {
+            let Panel::View(view) = panel else {
+                continue;
+            };
+            let Some(AggregateStatus::Running(progress)) = self.aggregates.get_mut(&view) else {
+                continue;
+            };
+
+            let mut policy = view
+                .policy
+                .build(self.config.minimax_depth, &mut self.minimax_cache);
+            let mut opponent = view.opponent.map(|opponent| {
+                opponent.build(self.config.minimax_depth, &mut self.opponent_minimax_cache)
+            });
+            let mut finished = false;
+            while timer.elapsed().as_secs_f64() < AGGREGATE_FRAME_BUDGET {
+                if !progress.step(&mut policy, opponent.as_mut()) {
+                    finished = true;
+                    break;
+                }
+            }
+
+            if finished {
+                if let Some(AggregateStatus::Running(progress)) = self.aggregates.remove(&view) {
+                    log::debug!("finished {} over {} states", view.name(), progress.total());
+                    self.aggregates
+                        .insert(view, AggregateStatus::Done(progress.finish()));
                 }
             }
         }
     }
 
+    /// The main view: the method chosen and the policy of the side to move.
+    fn main_view(&self) -> View {
+        View::new(self.explained_policy(), self.opponent_policy(), self.method)
+    }
+
+    /// Boards to draw from left to right.
+    fn panels(&self) -> Vec<Panel> {
+        let main = self.main_view();
+        let mut panels = Vec::new();
+        if let Method::Coalition = main.method {
+            panels.push(Panel::FullPolicy(main));
+        }
+        panels.push(Panel::View(main));
+
+        let Some(comparison) = self.comparison else {
+            return panels;
+        };
+
+        let other = comparison.other(main, self.opponent_policy());
+        panels.push(Panel::View(other));
+        // Only explanations of the same kind can be subtracted
+        let comparable = matches!(
+            (main.method, other.method),
+            (
+                Method::Shapley | Method::Semivalue { .. } | Method::Baseline { .. },
+                Method::Shapley | Method::Semivalue { .. } | Method::Baseline { .. }
+            ) | (
+                Method::Sverl { .. } | Method::StateValue,
+                Method::Sverl { .. } | Method::StateValue
+            ) | (Method::Aggregate { .. }, Method::Aggregate { .. })
+                | (Method::Coalition, Method::Coalition)
+                | (Method::Interaction { .. }, Method::Interaction { .. })
+        );
+        if comparable {
+            panels.push(Panel::Difference(main, other));
+        }
+        panels
+    }
+
+    fn boards_bounds(&self) -> Aabb2<f32> {
+        let board = self.model.bounds().map(|x| x as f32);
+        Aabb2 {
+            min: board.min,
+            max: board.max + board_offset(self.panels().len() - 1),
+        }
+    }
+
+    /// Returns the cell under the given screen position on any of the boards.
+    fn cell_at(&self, screen_pos: vec2<f32>) -> Option<vec2<Coord>> {
+        let world_pos = self
+            .camera
+            .screen_to_world(self.framebuffer_size.as_f32(), screen_pos);
+        (0..self.panels().len()).find_map(|i| {
+            let cell_pos = (world_pos - board_offset(i)).map(|x| x.floor() as isize);
+            if cell_pos.x < 0 || cell_pos.y < 0 {
+                return None;
+            }
+            let cell_pos = cell_pos.map(|x| x as Coord);
+            self.model.get(cell_pos).is_some().then_some(cell_pos)
+        })
+    }
+
+    fn view_value(&self, view: View, pos: vec2<Coord>, hovered: Option<vec2<Coord>>) -> f64 {
+        if let Method::Aggregate { .. } = view.method {
+            return match self.aggregates.get(&view) {
+                Some(AggregateStatus::Done(aggregate)) => {
+                    aggregate.mean.get(pos).copied().unwrap_or(0.0)
+                }
+                _ => 0.0,
+            };
+        }
+        // Values of each feature, showing the hovered one
+        let feature_value = |values: &Grid<Grid<f64>>| {
+            hovered
+                .and_then(|feature| values.get(feature))
+                .and_then(|grid| grid.get(pos))
+                .copied()
+                .unwrap_or(0.0)
+        };
+        match self.values.get(&view) {
+            Some(Values::Shapley(explanation)) => feature_value(&explanation.values),
+            Some(Values::Interaction { pairs, .. }) => feature_value(pairs),
+            Some(Values::Sverl(explanation)) => explanation.values.get(pos).copied().unwrap_or(0.0),
+            Some(Values::Coalition(explanation)) => {
+                explanation.values.get(pos).copied().unwrap_or(0.0)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn panel_value(&self, panel: Panel, pos: vec2<Coord>, hovered: Option<vec2<Coord>>) -> f64 {
+        match panel {
+            Panel::View(view) => self.view_value(view, pos, hovered),
+            Panel::FullPolicy(view) => match self.values.get(&view) {
+                Some(Values::Coalition(explanation)) => {
+                    explanation.full.get(pos).copied().unwrap_or(0.0)
+                }
+                _ => 0.0,
+            },
+            Panel::Difference(a, b) => {
+                self.view_value(a, pos, hovered) - self.view_value(b, pos, hovered)
+            }
+        }
+    }
+
+    fn side(&self, player: Player) -> &Side {
+        match player {
+            Player::X => &self.player_x,
+            Player::O => &self.player_o,
+        }
+    }
+
+    fn side_mut(&mut self, player: Player) -> &mut Side {
+        match player {
+            Player::X => &mut self.player_x,
+            Player::O => &mut self.player_o,
+        }
+    }
+
+    /// The policy of the side to move, which is the one being explained.
+    fn explained_policy(&self) -> Policy {
+        let player = self.model.current_player().unwrap_or(Player::X);
+        self.side(player).policy
+    }
+
+    /// The policy of the other side, which it follows in SVERL-P rollouts.
+    fn opponent_policy(&self) -> Policy {
+        let player = self.model.current_player().unwrap_or(Player::X);
+        self.side(player.next()).policy
+    }
+
     fn ai_move(&mut self) {
         let Some(player) = self.model.current_player() else {
             return;
         };
 
-        let action = match self.policy {
+        let action = match self.side(player).policy {
             Policy::Random => {
-                let action = random_action(&self.model);
+                let action = random_action(&self.model, &mut self.rng);
                 log::debug!("random chose action {:?}", action);
                 action
             }
             Policy::Minimax => {
-                let (action, value) =
-                    minimax_action(&self.model, &mut self.minimax_cache, player, None);
+                let (action, value) = minimax_action(
+                    &self.model,
+                    &mut self.minimax_cache,
+                    player,
+                    self.config.minimax_depth,
+                    &mut self.rng,
+                );
                 log::debug!("minimax chose action {:?} with value {:.2}", action, value);
                 action
             }
         };
-        self.model.set(action, player.into());
-        self.update_values(true);
+        self.play(player, action);
     }
 
     fn human_move(&mut self, pos: vec2<Coord>) {
@@ -265,12 +915,194 @@ impl State {
         let Some(player) = self.model.current_player() else {
             return;
         };
-        self.model.set(pos, player.into());
+        if self.side(player).controller != Controller::Human {
+            return;
+        }
+        self.autoplay_timer = 0.0;
+        self.play(player, pos);
+    }
+
+    /// Makes the move, continuing from the replayed position if there is one.
+    fn play(&mut self, player: Player, action: Action) {
+        let view = self.main_view();
+        if let Some(Values::Sverl(explanation)) = self.values.get(&view) {
+            self.explanations.push(PlyExplanation {
+                ply: self.history.len(),
+                explanation: (**explanation).clone(),
+            });
+        }
+        self.replay = None;
+        self.model.set(action, player.into());
+        self.history.push(action);
+        self.update_values(true);
+    }
+
+    fn player_record(&self, player: Player) -> PlayerRecord {
+        let side = self.side(player);
+        match side.controller {
+            Controller::Human => PlayerRecord {
+                policy: "human".to_string(),
+                minimax_depth: None,
+            },
+            Controller::Ai => PlayerRecord {
+                policy: side.policy.id().to_string(),
+                minimax_depth: self.minimax_depth(side.policy),
+            },
+        }
+    }
+
+    /// The depth limit of the policy's search.
+    fn minimax_depth(&self, policy: Policy) -> Option<usize> {
+        match policy {
+            Policy::Random => None,
+            Policy::Minimax => self.config.minimax_depth,
+        }
+    }
+
+    fn save_game(&self) {
+        let mut record = GameRecord::new(
+            self.opening.clone(),
+            self.history.clone(),
+            self.player_record(Player::X),
+            self.player_record(Player::O),
+            Some(self.seed),
+        );
+        record.explanations = self.explanations.clone();
+        let data = serde_json::to_vec_pretty(&vec![record]).expect("failed to serialize the game");
+        if let Err(err) = file_dialog::save("game.json", &data) {
+            log::error!("failed to save the game: {}", err);
+        }
+    }
+
+    /// Starts stepping through the games from a game record file.
+    pub fn load_games(&mut self, games: Vec<GameRecord>) -> Result<(), String> {
+        for (index, game) in games.iter().enumerate() {
+            game.validate()
+                .map_err(|err| format!("game {}: {}", index, err))?;
+        }
+        if games.is_empty() {
+            log::warn!("no games to replay");
+            return Ok(());
+        }
+        self.replay = Some(Replay {
+            games,
+            game: 0,
+            ply: 0,
+        });
+        self.autoplay = false;
+        self.seek_replay(0, 0);
+        Ok(())
+    }
+
+    /// Shows the position after `ply` moves of the replayed `game`.
+    fn seek_replay(&mut self, game: usize, ply: usize) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        replay.game = game.min(replay.games.len() - 1);
+        let record = &replay.games[replay.game];
+        replay.ply = ply.min(record.moves.len());
+
+        self.model = record.board(replay.ply);
+        self.opening = record.opening.clone();
+        self.history = record.moves[..replay.ply].to_vec();
+        self.explanations = record
+            .explanations
+            .iter()
+            .filter(|explanation| explanation.ply < replay.ply)
+            .cloned()
+            .collect();
+        if let Some(seed) = record.seed {
+            (self.seed, self.rng) = seeded_rng(Some(seed));
+        }
+        self.update_values(true);
+    }
+
+    fn update_autoplay(&mut self, delta_time: f64) {
+        if !self.autoplay || self.replay.is_some() {
+            return;
+        }
+
+        let ai_turn = match self.model.current_player() {
+            Some(player) => self.side(player).controller == Controller::Ai,
+            // Only start a new game by itself when neither side is human
+            None => {
+                self.player_x.controller == Controller::Ai
+                    && self.player_o.controller == Controller::Ai
+            }
+        };
+        if !ai_turn {
+            self.autoplay_timer = 0.0;
+            return;
+        }
+
+        self.autoplay_timer += delta_time;
+        if self.autoplay_timer < self.autoplay_delay {
+            return;
+        }
+        self.autoplay_timer = 0.0;
+
+        if self.model.current_player().is_some() {
+            self.ai_move();
+        } else {
+            self.reset();
+        }
+    }
+
+    fn toggle_controller(&mut self, player: Player) {
+        let side = self.side_mut(player);
+        side.controller = match side.controller {
+            Controller::Human => Controller::Ai,
+            Controller::Ai => Controller::Human,
+        };
+        self.autoplay_timer = 0.0;
+    }
+
+    fn cycle_policy(&mut self, player: Player) {
+        let side = self.side_mut(player);
+        side.policy = side.policy.next();
         self.update_values(true);
     }
 
+    fn toggle_hidden(&mut self, pos: vec2<Coord>) {
+        if let Some(i) = self.hidden.iter().position(|&p| p == pos) {
+            self.hidden.swap_remove(i);
+        } else {
+            self.hidden.push(pos);
+        }
+        self.values
+            .retain(|view, _| !matches!(view.method, Method::Coalition));
+        self.update_values(false);
+    }
+
+    fn set_gamma(&mut self, gamma: f64) {
+        self.config.gamma = gamma.clamp(0.0, 1.0);
+        self.values.retain(|view, _| !view.method.uses_gamma());
+        self.aggregates.clear();
+        self.update_values(false);
+    }
+
+    fn set_minimax_depth(&mut self, depth: Option<usize>) {
+        self.config.minimax_depth = depth;
+        self.values.retain(|view, _| {
+            view.policy != Policy::Minimax && view.opponent != Some(Policy::Minimax)
+        });
+        self.aggregates
+            .retain(|view, _| view.policy != Policy::Minimax);
+        self.counterfactuals.remove(&Policy::Minimax);
+        self.update_values(false);
+    }
+
     fn reset(&mut self) {
         self.model = Grid::new();
+        self.opening = Grid::new();
+        self.history.clear();
+        self.explanations.clear();
+        self.replay = None;
+        // Derived from the previous game, so that all games follow from the initial seed
+        (self.seed, self.rng) = seeded_rng(Some(self.rng.gen()));
+        log::debug!("game seed: {}", self.seed);
+        self.autoplay_timer = 0.0;
         self.update_values(true);
     }
 
@@ -279,42 +1111,328 @@ impl State {
             return;
         };
 
-        if self.ui.policy_random.contains(pos) {
-            self.policy = Policy::Random;
-            self.update_values(true);
-        } else if self.ui.policy_minimax.contains(pos) {
-            self.policy = Policy::Minimax;
-            self.update_values(true);
+        if self.ui.player_x_controller.contains(pos) {
+            self.toggle_controller(Player::X);
+        } else if self.ui.player_x_policy.contains(pos) {
+            self.cycle_policy(Player::X);
+        } else if self.ui.player_o_controller.contains(pos) {
+            self.toggle_controller(Player::O);
+        } else if self.ui.player_o_policy.contains(pos) {
+            self.cycle_policy(Player::O);
         } else if self.ui.method_shapley.contains(pos) {
-            self.method = Method::Shapley;
+            // Cycles through the baselines once Shapley is shown
+            self.method = match self.method {
+                Method::Shapley => Method::Baseline { lime: false },
+                Method::Baseline { lime: false } => Method::Baseline { lime: true },
+                _ => Method::Shapley,
+            };
             self.update_values(false);
         } else if self.ui.method_sverl.contains(pos) {
             self.method = Method::Sverl {
                 global: self.sverl_global,
             };
             self.update_values(false);
+        } else if self.ui.method_state_value.contains(pos) {
+            self.method = Method::StateValue;
+            self.update_values(false);
+        } else if self.ui.method_semivalue.contains(pos) {
+            self.method = Method::Semivalue { beta: false };
+            self.update_values(false);
         } else if self.ui.board_reset.contains(pos) {
             self.reset();
         } else if self.ui.board_policy_turn.contains(pos) {
             self.ai_move();
+        } else if self.ui.method_coalition.contains(pos) {
+            self.method = Method::Coalition;
+            self.update_values(false);
+        } else if self.ui.method_interaction.contains(pos) {
+            self.method = Method::Interaction { sverl: false };
+            self.update_values(false);
+        } else if self.ui.compare.contains(pos) {
+            self.comparison = match self.comparison {
+                None => Some(Comparison::Method),
+                Some(Comparison::Method) => Some(Comparison::Policy),
+                Some(Comparison::Policy) => None,
+            };
+            self.update_values(false);
+        } else if self.ui.gamma_decrease.contains(pos) {
+            self.set_gamma(self.config.gamma - 0.05);
+        } else if self.ui.gamma_increase.contains(pos) {
+            self.set_gamma(self.config.gamma + 0.05);
+        } else if self.ui.depth_decrease.contains(pos) {
+            let depth = match self.config.minimax_depth {
+                None => Some(MAX_MINIMAX_DEPTH),
+                Some(depth) => Some(depth.saturating_sub(1)),
+            };
+            self.set_minimax_depth(depth);
+        } else if self.ui.depth_increase.contains(pos) {
+            let depth = self
+                .config
+                .minimax_depth
+                .and_then(|depth| (depth < MAX_MINIMAX_DEPTH).then_some(depth + 1));
+            self.set_minimax_depth(depth);
+        } else if self.ui.autoplay.contains(pos) {
+            self.autoplay = !self.autoplay;
+            self.autoplay_timer = 0.0;
+        } else if self.ui.autoplay_slower.contains(pos) {
+            self.autoplay_delay = (self.autoplay_delay * 2.0).min(4.0);
+        } else if self.ui.autoplay_faster.contains(pos) {
+            self.autoplay_delay = (self.autoplay_delay / 2.0).max(1.0 / 32.0);
         } else if self.ui.method_sverl_global.contains(pos) {
-            if let Method::Sverl { global } = &mut self.method {
+            if let Method::Sverl { global } | Method::Aggregate { global } = &mut self.method {
                 *global = !*global;
                 self.sverl_global = *global;
                 self.update_values(false);
             }
-        } else {
-            let world_pos = self
-                .camera
-                .screen_to_world(self.framebuffer_size.as_f32(), pos.as_f32());
-            let cell_pos = world_pos.map(|x| x.floor() as isize);
-            if cell_pos.x >= 0 && cell_pos.y >= 0 {
-                let cell_pos = cell_pos.map(|x| x as Coord);
+        } else if self.ui.method_sverl_average.contains(pos) {
+            self.method = match self.method {
+                Method::Sverl { global } => Method::Aggregate { global },
+                Method::Aggregate { global } => Method::Sverl { global },
+                method => method,
+            };
+            self.update_values(false);
+        } else if self.ui.method_semivalue_beta.contains(pos) {
+            if let Method::Semivalue { beta } = &mut self.method {
+                *beta = !*beta;
+                self.update_values(false);
+            }
+        } else if self.ui.method_interaction_sverl.contains(pos) {
+            if let Method::Interaction { sverl } = &mut self.method {
+                *sverl = !*sverl;
+                self.update_values(false);
+            }
+        } else if let Some(cell_pos) = self.cell_at(pos) {
+            if let Method::Coalition = self.method {
+                self.toggle_hidden(cell_pos);
+            } else {
                 self.human_move(cell_pos);
             }
         }
     }
 
+    fn update_camera(&mut self, portrait: bool) {
+        let bounds = self.boards_bounds();
+        let framebuffer_size = self.framebuffer_size.as_f32();
+        let aspect = framebuffer_size.x / framebuffer_size.y;
+
+        self.camera.center = bounds.center();
+        if portrait {
+            // Fit all boards into the screen width below the buttons
+            self.camera.fov = (bounds.width() / 0.9 / aspect).max(10.0);
+            self.camera.center.y += self.camera.fov * 0.2;
+        } else if self.comparison.is_none() {
+            self.camera.fov = 7.0;
+        } else {
+            // Keep the boards to the right of the buttons
+            self.camera.fov = (bounds.width() / 0.55 / aspect).max(7.0);
+            let visible_width = self.camera.fov * aspect;
+            self.camera.center.x = bounds.min.x + 0.1 * visible_width;
+        }
+    }
+
+    fn draw_board(
+        &self,
+        panel: Panel,
+        offset: vec2<f32>,
+        hovered: Option<vec2<Coord>>,
+        framebuffer: &mut ugli::Framebuffer,
+    ) {
+        // Grid lines
+        let bounds = self.model.bounds();
+        for x in 1..bounds.width() {
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &self.camera,
+                &draw2d::Segment::new(
+                    Segment(
+                        vec2(x, bounds.min.y).as_f32() + offset,
+                        vec2(x, bounds.max.y).as_f32() + offset,
+                    ),
+                    0.1,
+                    self.config.palette.grid,
+                ),
+            );
+        }
+        for y in 1..bounds.height() {
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &self.camera,
+                &draw2d::Segment::new(
+                    Segment(
+                        vec2(bounds.min.x, y).as_f32() + offset,
+                        vec2(bounds.max.x, y).as_f32() + offset,
+                    ),
+                    0.1,
+                    self.config.palette.grid,
+                ),
+            );
+        }
+
+        // Cells
+        // let minimax = self.model.current_player().map_or(Grid::zero(), |player| {
+        //     minimax(&self.model, &mut self.minimax_cache, player, None, 0)
+        // });
+        for pos in self.model.positions() {
+            if let Some(cell) = self.model.get(pos) {
+                let value = self.panel_value(panel, pos, hovered) as f32;
+
+                let ratio = 0.9;
+                let aabb = Aabb2::point(pos.as_f32() + offset + vec2(0.5, 0.5))
+                    .extend_symmetric(vec2(ratio, ratio) / 2.0);
+                let mut negative = self.config.palette.eval_negative;
+                negative.a = -value;
+                let mut positive = self.config.palette.eval_positive;
+                positive.a = value;
+                let color = if value > 0.0 { positive } else { negative };
+                self.geng
+                    .draw2d()
+                    .quad(framebuffer, &self.camera, aabb, color);
+
+                match cell {
+                    Tile::Empty => {}
+                    Tile::X => {
+                        self.draw_x(pos.as_f32() + offset, 1.0, framebuffer);
+                    }
+                    Tile::O => {
+                        self.draw_o(pos.as_f32() + offset, 1.0, framebuffer);
+                    }
+                }
+
+                if let Method::Coalition = self.method {
+                    if self.hidden.contains(&pos) {
+                        let mut color = self.config.palette.background;
+                        color.a = 0.8;
+                        self.geng
+                            .draw2d()
+                            .quad(framebuffer, &self.camera, aabb, color);
+                        self.geng.default_font().draw(
+                            framebuffer,
+                            &self.camera,
+                            "?",
+                            vec2::splat(geng::TextAlign::CENTER),
+                            mat3::translate(aabb.center()) * mat3::scale_uniform(0.5),
+                            self.config.palette.text,
+                        );
+                    } else if self.model.check(pos) {
+                        // Action probabilities
+                        self.geng.default_font().draw(
+                            framebuffer,
+                            &self.camera,
+                            &format!("{:.2}", value),
+                            vec2::splat(geng::TextAlign::CENTER),
+                            mat3::translate(aabb.center()) * mat3::scale_uniform(0.3),
+                            self.config.palette.text,
+                        );
+                    }
+                }
+
+                // if self.model.check(pos) {
+                //     let value = minimax.get(pos).unwrap();
+                //     self.geng.default_font().draw(
+                //         framebuffer,
+                //         &self.camera,
+                //         &format!("{:+.2}", value),
+                //         vec2::splat(geng::TextAlign::CENTER),
+                //         mat3::translate(pos.as_f32() + vec2(0.5, 0.5)) * mat3::scale_uniform(0.3),
+                //         self.config.palette.text,
+                //     );
+                // }
+            }
+        }
+
+        if let Panel::View(view) = panel {
+            if let Some(Values::Interaction { pairs, .. }) = self.values.get(&view) {
+                self.draw_links(pairs, offset, framebuffer);
+            }
+        }
+    }
+
+    /// Fades the edited cells, with the pieces after the edits drawn over them.
+    fn draw_counterfactual(
+        &self,
+        counterfactual: &Counterfactual,
+        offset: vec2<f32>,
+        framebuffer: &mut ugli::Framebuffer,
+    ) {
+        for edit in &counterfactual.edits {
+            let pos = edit.position().as_f32() + offset;
+            let aabb = Aabb2::point(pos + vec2(0.5, 0.5)).extend_symmetric(vec2(0.9, 0.9) / 2.0);
+            let mut color = self.config.palette.background;
+            color.a = 0.7;
+            self.geng
+                .draw2d()
+                .quad(framebuffer, &self.camera, aabb, color);
+            match edit.tile() {
+                Tile::Empty => {}
+                Tile::X => self.draw_x(pos, 0.6, framebuffer),
+                Tile::O => self.draw_o(pos, 0.6, framebuffer),
+            }
+            let corners = [
+                aabb.bottom_left(),
+                aabb.bottom_right(),
+                aabb.top_right(),
+                aabb.top_left(),
+            ];
+            for (i, &corner) in corners.iter().enumerate() {
+                self.geng.draw2d().draw2d(
+                    framebuffer,
+                    &self.camera,
+                    &draw2d::Segment::new(
+                        Segment(corner, corners[(i + 1) % corners.len()]),
+                        0.05,
+                        self.config.palette.button_border_active,
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Draws the interactions between pairs of cells as links, scaled by the strongest one.
+    fn draw_links(
+        &self,
+        values: &Grid<Grid<f64>>,
+        offset: vec2<f32>,
+        framebuffer: &mut ugli::Framebuffer,
+    ) {
+        let positions: Vec<_> = self.model.positions().collect();
+        let pairs = || {
+            positions.iter().enumerate().flat_map(|(t, &a)| {
+                positions[t + 1..]
+                    .iter()
+                    .map(move |&b| (a, b, *values.get(a).unwrap().get(b).unwrap()))
+            })
+        };
+        let max = pairs().map(|(_, _, value)| r64(value.abs())).max();
+        let Some(max) = max.map(|max| max.raw()).filter(|&max| max > 1e-9) else {
+            return;
+        };
+
+        for (a, b, value) in pairs() {
+            let strength = (value / max) as f32;
+            if strength.abs() < 0.1 {
+                continue;
+            }
+            let mut color = if strength > 0.0 {
+                self.config.palette.eval_positive
+            } else {
+                self.config.palette.eval_negative
+            };
+            color.a = strength.abs();
+            self.geng.draw2d().draw2d(
+                framebuffer,
+                &self.camera,
+                &draw2d::Segment::new(
+                    Segment(
+                        a.as_f32() + offset + vec2(0.5, 0.5),
+                        b.as_f32() + offset + vec2(0.5, 0.5),
+                    ),
+                    0.05 + 0.15 * strength.abs(),
+                    color,
+                ),
+            );
+        }
+    }
+
     fn draw_ui(&mut self, framebuffer: &mut ugli::Framebuffer) {
         let camera = &geng::PixelPerfectCamera;
         let font_size = self.ui.font_size;
@@ -353,31 +1471,112 @@ impl State {
             );
         };
 
+        for (player, side, controller, policy) in [
+            (
+                Player::X,
+                &self.player_x,
+                self.ui.player_x_controller,
+                self.ui.player_x_policy,
+            ),
+            (
+                Player::O,
+                &self.player_o,
+                self.ui.player_o_controller,
+                self.ui.player_o_policy,
+            ),
+        ] {
+            let controller_name = match side.controller {
+                Controller::Human => "Human",
+                Controller::Ai => "AI",
+            };
+            draw_button(
+                &format!("{:?}: {}", player, controller_name),
+                controller,
+                side.controller == Controller::Ai,
+            );
+            draw_button(
+                &format!("{:?}: {}", player, side.policy.name()),
+                policy,
+                self.model.current_player() == Some(player),
+            );
+        }
+        let shapley = match self.method {
+            Method::Baseline { .. } => format!("Method: {}", self.method.name()),
+            _ => "Method: Shapley".to_string(),
+        };
         draw_button(
-            "Policy: Random",
-            self.ui.policy_random,
-            matches!(self.policy, Policy::Random),
+            &shapley,
+            self.ui.method_shapley,
+            matches!(self.method, Method::Shapley | Method::Baseline { .. }),
         );
         draw_button(
-            "Policy: Minimax",
-            self.ui.policy_minimax,
-            matches!(self.policy, Policy::Minimax),
+            "Method: SVERL-P",
+            self.ui.method_sverl,
+            matches!(self.method, Method::Sverl { .. } | Method::Aggregate { .. }),
         );
         draw_button(
-            "Method: Shapley",
-            self.ui.method_shapley,
-            matches!(self.method, Method::Shapley),
+            "Method: Value",
+            self.ui.method_state_value,
+            matches!(self.method, Method::StateValue),
         );
         draw_button(
-            "Method: SVERL-P",
-            self.ui.method_sverl,
-            matches!(self.method, Method::Sverl { .. }),
+            "Method: Semivalue",
+            self.ui.method_semivalue,
+            matches!(self.method, Method::Semivalue { .. }),
+        );
+        draw_button(
+            "Method: Coalition",
+            self.ui.method_coalition,
+            matches!(self.method, Method::Coalition),
+        );
+        draw_button(
+            "Method: Interaction",
+            self.ui.method_interaction,
+            matches!(self.method, Method::Interaction { .. }),
         );
         draw_button("Board Reset", self.ui.board_reset, false);
         draw_button("Policy Turn", self.ui.board_policy_turn, false);
+        let compare = match self.comparison {
+            None => "Compare: Off",
+            Some(Comparison::Method) => "Compare: Method",
+            Some(Comparison::Policy) => "Compare: Policy",
+        };
+        draw_button(compare, self.ui.compare, self.comparison.is_some());
+        draw_button(
+            &format!("Gamma: {:.2}", self.config.gamma),
+            self.ui.gamma,
+            false,
+        );
+        draw_button("-", self.ui.gamma_decrease, false);
+        draw_button("+", self.ui.gamma_increase, false);
+        let depth = match self.config.minimax_depth {
+            None => "Depth: Full".to_string(),
+            Some(depth) => format!("Depth: {}", depth),
+        };
+        draw_button(&depth, self.ui.depth, false);
+        draw_button("-", self.ui.depth_decrease, false);
+        draw_button("+", self.ui.depth_increase, false);
+        draw_button(
+            &format!("Auto: {:.2}s", self.autoplay_delay),
+            self.ui.autoplay,
+            self.autoplay,
+        );
+        draw_button("Slower", self.ui.autoplay_slower, false);
+        draw_button("Faster", self.ui.autoplay_faster, false);
 
-        if let Method::Sverl { global } = self.method {
+        if let Method::Sverl { global } | Method::Aggregate { global } = self.method {
             draw_button("Global", self.ui.method_sverl_global, global);
+            draw_button(
+                "Average",
+                self.ui.method_sverl_average,
+                matches!(self.method, Method::Aggregate { .. }),
+            );
+        }
+        if let Method::Interaction { sverl } = self.method {
+            draw_button("SVERL", self.ui.method_interaction_sverl, sverl);
+        }
+        if let Method::Semivalue { beta } = self.method {
+            draw_button("Beta", self.ui.method_semivalue_beta, beta);
         }
     }
 }
@@ -385,7 +1584,8 @@ impl State {
 impl geng::State for State {
     fn update(&mut self, delta_time: f64) {
         self.touch.update(delta_time);
-        self.camera.center = self.model.bounds().map(|x| x as f32).center();
+        self.update_autoplay(delta_time);
+        self.update_aggregates();
     }
 
     fn handle_event(&mut self, event: geng::Event) {
@@ -406,6 +1606,31 @@ impl geng::State for State {
                 geng::Key::R => {
                     self.reset();
                 }
+                geng::Key::A => {
+                    self.autoplay = !self.autoplay;
+                    self.autoplay_timer = 0.0;
+                }
+                geng::Key::S => {
+                    self.save_game();
+                }
+                geng::Key::C => {
+                    self.show_counterfactuals = !self.show_counterfactuals;
+                    self.update_counterfactuals();
+                }
+                geng::Key::ArrowLeft
+                | geng::Key::ArrowRight
+                | geng::Key::ArrowUp
+                | geng::Key::ArrowDown => {
+                    if let Some(replay) = &self.replay {
+                        let (game, ply) = match key {
+                            geng::Key::ArrowLeft => (replay.game, replay.ply.saturating_sub(1)),
+                            geng::Key::ArrowRight => (replay.game, replay.ply + 1),
+                            geng::Key::ArrowUp => (replay.game.saturating_sub(1), 0),
+                            _ => (replay.game + 1, 0),
+                        };
+                        self.seek_replay(game, ply);
+                    }
+                }
                 _ => {}
             },
             geng::Event::MousePress { button } => {
@@ -422,14 +1647,10 @@ impl geng::State for State {
 
     fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
         let portrait = framebuffer.size().x < framebuffer.size().y;
-        if portrait {
-            self.camera.fov = 10.0;
-        } else {
-            self.camera.fov = 7.0;
-        };
         self.ui.layout(portrait, framebuffer.size().as_f32());
-
         self.framebuffer_size = framebuffer.size();
+        self.update_camera(portrait);
+
         ugli::clear(
             framebuffer,
             Some(self.config.palette.background),
@@ -437,109 +1658,154 @@ impl geng::State for State {
             None,
         );
 
-        // Grid lines
-        let bounds = self.model.bounds();
-        for x in 1..bounds.width() {
-            self.geng.draw2d().draw2d(
+        let panels = self.panels();
+        let hovered = self.cell_at(self.ui.cursor_pos);
+        for (i, &panel) in panels.iter().enumerate() {
+            self.draw_board(panel, board_offset(i), hovered, framebuffer);
+            if panels.len() > 1 {
+                let name = match panel {
+                    Panel::View(view) => view.name(),
+                    Panel::FullPolicy(view) => format!("{} Full", view.policy.name()),
+                    Panel::Difference(..) => "Difference".to_string(),
+                };
+                self.geng.default_font().draw(
+                    framebuffer,
+                    &self.camera,
+                    &name,
+                    vec2::splat(geng::TextAlign::CENTER),
+                    mat3::translate(board_offset(i) + vec2(1.5, -0.5)) * mat3::scale_uniform(0.35),
+                    self.config.palette.text,
+                );
+            }
+        }
+
+        let center = self.boards_bounds().center();
+        if self.show_counterfactuals {
+            let main = self.main_view();
+            let index = panels
+                .iter()
+                .position(|panel| matches!(panel, Panel::View(view) if *view == main));
+            let counterfactuals = self.counterfactuals.get(&main.policy);
+            if let (Some(index), Some(first)) = (
+                index,
+                counterfactuals.and_then(|counterfactuals| counterfactuals.first()),
+            ) {
+                self.draw_counterfactual(first, board_offset(index), framebuffer);
+            }
+            if let Some(counterfactuals) = counterfactuals {
+                let text = match counterfactuals.first() {
+                    Some(first) => {
+                        let edits: Vec<_> =
+                            first.edits.iter().map(|edit| edit.to_string()).collect();
+                        format!(
+                            "Plays elsewhere if: {} (1 of {})",
+                            edits.join(", "),
+                            counterfactuals.len()
+                        )
+                    }
+                    None => "No legal board changes the top action".to_string(),
+                };
+                self.geng.default_font().draw(
+                    framebuffer,
+                    &self.camera,
+                    &text,
+                    vec2::splat(geng::TextAlign::CENTER),
+                    mat3::translate(center + vec2(0.0, 3.1)) * mat3::scale_uniform(0.3),
+                    self.config.palette.text,
+                );
+            }
+        }
+
+        if let Some(player) = self.model.current_player() {
+            self.geng.default_font().draw(
                 framebuffer,
                 &self.camera,
-                &draw2d::Segment::new(
-                    Segment(
-                        vec2(x, bounds.min.y).as_f32(),
-                        vec2(x, bounds.max.y).as_f32(),
+                &match self.main_view().opponent {
+                    Some(opponent) => format!(
+                        "Explaining {:?}: {} vs {}",
+                        player,
+                        self.side(player).policy.name(),
+                        opponent.name()
                     ),
-                    0.1,
-                    self.config.palette.grid,
-                ),
+                    None => format!(
+                        "Explaining {:?}: {}",
+                        player,
+                        self.side(player).policy.name()
+                    ),
+                },
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(center + vec2(0.0, 2.0)) * mat3::scale_uniform(0.5),
+                self.config.palette.text,
             );
         }
-        for y in 1..bounds.height() {
-            self.geng.draw2d().draw2d(
+
+        if let Some(replay) = &self.replay {
+            self.geng.default_font().draw(
                 framebuffer,
                 &self.camera,
-                &draw2d::Segment::new(
-                    Segment(
-                        vec2(bounds.min.x, y).as_f32(),
-                        vec2(bounds.max.x, y).as_f32(),
-                    ),
-                    0.1,
-                    self.config.palette.grid,
+                &format!(
+                    "Replay: game {}/{}, move {}/{}",
+                    replay.game + 1,
+                    replay.games.len(),
+                    replay.ply,
+                    replay.games[replay.game].moves.len()
                 ),
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(center + vec2(0.0, 2.6)) * mat3::scale_uniform(0.35),
+                self.config.palette.text,
             );
         }
 
-        // Cells
-        // let minimax = self.model.current_player().map_or(Grid::zero(), |player| {
-        //     minimax(&self.model, &mut self.minimax_cache, player, None, 0)
-        // });
-        for pos in self.model.positions() {
-            if let Some(cell) = self.model.get(pos) {
-                let value = match self.method {
-                    Method::Shapley => {
-                        let mouse_pos = self.ui.cursor_pos;
-                        let mouse_pos = self
-                            .camera
-                            .screen_to_world(self.framebuffer_size.as_f32(), mouse_pos.as_f32());
-                        let cell_pos = mouse_pos.map(|x| x.floor() as isize);
-                        (cell_pos.x >= 0 && cell_pos.y >= 0)
-                            .then(|| {
-                                let cell_pos = cell_pos.map(|x| x as Coord);
-                                self.shapley_values
-                                    .as_ref()
-                                    .and_then(|values| values.get(cell_pos))
-                                    .and_then(|grid| grid.get(pos))
-                                    .copied()
-                            })
-                            .flatten()
-                            .unwrap_or(0.0) as f32
-                    }
-                    Method::Sverl { global } => {
-                        let values = if global {
-                            &self.sverl_values_global
-                        } else {
-                            &self.sverl_values_local
-                        };
-                        values
-                            .as_ref()
-                            .and_then(|values| values.get(pos))
-                            .copied()
-                            .unwrap_or(0.0) as f32
-                    }
-                };
-
-                let ratio = 0.9;
-                let aabb = Aabb2::point(pos.as_f32() + vec2(0.5, 0.5))
-                    .extend_symmetric(vec2(ratio, ratio) / 2.0);
-                let mut negative = self.config.palette.eval_negative;
-                negative.a = -value;
-                let mut positive = self.config.palette.eval_positive;
-                positive.a = value;
-                let color = if value > 0.0 { positive } else { negative };
-                self.geng
-                    .draw2d()
-                    .quad(framebuffer, &self.camera, aabb, color);
-
-                match cell {
-                    Tile::Empty => {}
-                    Tile::X => {
-                        self.draw_x(pos, 1.0, framebuffer);
-                    }
-                    Tile::O => {
-                        self.draw_o(pos, 1.0, framebuffer);
-                    }
-                }
+        let residual = match self.values.get(&self.main_view()) {
+            Some(Values::Shapley(explanation)) => {
+                Some((explanation.efficiency_residual, explanation.is_efficient()))
+            }
+            Some(Values::Sverl(explanation)) => {
+                Some((explanation.efficiency_residual, explanation.is_efficient()))
+            }
+            Some(Values::Interaction { explanation, .. }) => {
+                Some((explanation.efficiency_residual, explanation.is_efficient()))
+            }
+            _ => None,
+        };
+        if let Some((residual, efficient)) = residual {
+            let note = if efficient { "" } else { " (not efficient)" };
+            self.geng.default_font().draw(
+                framebuffer,
+                &self.camera,
+                &format!("Efficiency residual: {:.1e}{}", residual, note),
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(center + vec2(0.0, -3.2)) * mat3::scale_uniform(0.3),
+                self.config.palette.text,
+            );
+        }
 
-                // if self.model.check(pos) {
-                //     let value = minimax.get(pos).unwrap();
-                //     self.geng.default_font().draw(
-                //         framebuffer,
-                //         &self.camera,
-                //         &format!("{:+.2}", value),
-                //         vec2::splat(geng::TextAlign::CENTER),
-                //         mat3::translate(pos.as_f32() + vec2(0.5, 0.5)) * mat3::scale_uniform(0.3),
-                //         self.config.palette.text,
-                //     );
-                // }
+        if let Method::Aggregate { .. } = self.method {
+            let text = match self.aggregates.get(&self.main_view()) {
+                Some(AggregateStatus::Running(progress)) => Some(format!(
+                    "Averaging: {}/{} states",
+                    progress.done(),
+                    progress.total()
+                )),
+                Some(AggregateStatus::Done(aggregate)) => hovered.map(|pos| {
+                    format!(
+                        "10%: {:+.3}  Median: {:+.3}  90%: {:+.3}",
+                        aggregate.quantile(pos, 0.1),
+                        aggregate.quantile(pos, 0.5),
+                        aggregate.quantile(pos, 0.9)
+                    )
+                }),
+                None => None,
+            };
+            if let Some(text) = text {
+                self.geng.default_font().draw(
+                    framebuffer,
+                    &self.camera,
+                    &text,
+                    vec2::splat(geng::TextAlign::CENTER),
+                    mat3::translate(center + vec2(0.0, -3.2)) * mat3::scale_uniform(0.4),
+                    self.config.palette.text,
+                );
             }
         }
 
@@ -549,44 +1815,39 @@ impl geng::State for State {
                 &self.camera,
                 &format!("Winner {:?}", winner),
                 vec2::splat(geng::TextAlign::CENTER),
-                mat3::translate(self.camera.center + vec2(0.0, -4.0)) * mat3::scale_uniform(1.0),
+                mat3::translate(center + vec2(0.0, -4.0)) * mat3::scale_uniform(1.0),
                 self.config.palette.text,
             );
-        } else {
+        } else if let Some(cell_pos) = hovered {
             // Display minimax evaluation
-            let mouse_pos = self.ui.cursor_pos;
-            let mouse_pos = self
-                .camera
-                .screen_to_world(self.framebuffer_size.as_f32(), mouse_pos.as_f32());
-            let cell_pos = mouse_pos.map(|x| x.floor() as isize);
-            if cell_pos.x >= 0 && cell_pos.y >= 0 {
-                let cell_pos = cell_pos.map(|x| x as Coord);
-                if let Some(Tile::Empty) = self.model.get(cell_pos) {
-                    let mut grid = self.model.clone();
-                    if let Some(player) = grid.current_player() {
-                        grid.set(cell_pos, player.into());
-                        let (_action, value) =
-                            minimax_action(&grid, &mut self.minimax_cache, player.next(), None);
-                        let value = match player {
-                            Player::X => -value,
-                            Player::O => value,
-                        };
-
-                        match player {
-                            Player::X => self.draw_x(cell_pos, 0.5, framebuffer),
-                            Player::O => self.draw_o(cell_pos, 0.5, framebuffer),
-                        }
+            if let Some(Tile::Empty) = self.model.get(cell_pos) {
+                let mut grid = self.model.clone();
+                if let Some(player) = grid.current_player() {
+                    grid.set(cell_pos, player.into());
+                    let value = minimax_value(
+                        &grid,
+                        &mut self.minimax_cache,
+                        player.next(),
+                        self.config.minimax_depth,
+                    );
+                    let value = match player {
+                        Player::X => -value,
+                        Player::O => value,
+                    };
 
-                        self.geng.default_font().draw(
-                            framebuffer,
-                            &self.camera,
-                            &format!("Minimax evaluation: {:+.2}", value),
-                            vec2::splat(geng::TextAlign::CENTER),
-                            mat3::translate(self.camera.center + vec2(0.0, -4.0))
-                                * mat3::scale_uniform(0.6),
-                            self.config.palette.text,
-                        );
+                    match player {
+                        Player::X => self.draw_x(cell_pos.as_f32(), 0.5, framebuffer),
+                        Player::O => self.draw_o(cell_pos.as_f32(), 0.5, framebuffer),
                     }
+
+                    self.geng.default_font().draw(
+                        framebuffer,
+                        &self.camera,
+                        &format!("Minimax evaluation: {:+.2}", value),
+                        vec2::splat(geng::TextAlign::CENTER),
+                        mat3::translate(center + vec2(0.0, -4.0)) * mat3::scale_uniform(0.6),
+                        self.config.palette.text,
+                    );
                 }
             }
         }
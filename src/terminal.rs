@@ -0,0 +1,119 @@
+use crate::{
+    tictactoe::{Grid, Tile},
+    Palette,
+};
+
+use std::io::IsTerminal;
+
+use geng::prelude::*;
+
+/// Width of a cell in characters.
+const CELL_WIDTH: usize = 9;
+/// Space between boards drawn side by side.
+const GAP: &str = "   ";
+
+/// Whether the terminal can show colors, see <https://no-color.org>
+/// and <https://github.com/termstandard/colors>.
+pub fn supports_truecolor() -> bool {
+    let truecolor = std::env::var("COLORTERM")
+        .is_ok_and(|term| term.contains("truecolor") || term.contains("24bit"));
+    truecolor && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+}
+
+/// Heatmap of the board, only the numbers without a palette.
+pub fn board_lines(palette: Option<&Palette>, board: &Grid, values: &Grid<f64>) -> Vec<String> {
+    let mut lines = Vec::new();
+    for y in (0..board.bounds().height()).rev() {
+        if palette.is_none() && !lines.is_empty() {
+            let cells = vec!["-".repeat(CELL_WIDTH); board.bounds().width()];
+            lines.push(cells.join("+"));
+        }
+        let cells: Vec<_> = (0..board.bounds().width())
+            .map(|x| {
+                let pos = vec2(x, y);
+                cell(palette, board.get(pos).unwrap(), *values.get(pos).unwrap())
+            })
+            .collect();
+        lines.push(cells.join(if palette.is_some() { "" } else { "|" }));
+    }
+    lines
+}
+
+/// One heatmap per feature, placed like the feature on the board.
+pub fn small_multiples_lines(
+    palette: Option<&Palette>,
+    board: &Grid,
+    values: &Grid<Grid<f64>>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    for y in (0..board.bounds().height()).rev() {
+        let boards: Vec<_> = (0..board.bounds().width())
+            .map(|x| {
+                let feature = vec2(x, y);
+                let values = values.get(feature).expect("feature outside the grid");
+                let index = board
+                    .reading_order()
+                    .position(|pos| pos == feature)
+                    .unwrap();
+                let mut board_lines = board_lines(palette, board, values);
+                let columns = board.bounds().width();
+                let separators = if palette.is_some() { 0 } else { columns - 1 };
+                let width = CELL_WIDTH * columns + separators;
+                board_lines.insert(0, format!("{:<width$}", format!("cell {}", index)));
+                board_lines
+            })
+            .collect();
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        for row in 0..boards[0].len() {
+            let row: Vec<_> = boards.iter().map(|lines| lines[row].as_str()).collect();
+            lines.push(row.join(GAP));
+        }
+    }
+    lines
+}
+
+fn cell(palette: Option<&Palette>, tile: &Tile, value: f64) -> String {
+    let piece = match tile {
+        Tile::Empty => '.',
+        Tile::X => 'x',
+        Tile::O => 'o',
+    };
+    let text = format!(" {} {:+.2} ", piece, value);
+    let Some(palette) = palette else {
+        return text;
+    };
+
+    // Terminals have no transparency, so blend with the background like the GUI does
+    let eval = if value > 0.0 {
+        palette.eval_positive
+    } else {
+        palette.eval_negative
+    };
+    let t = (value.abs() as f32).min(1.0);
+    let background = palette.background;
+    let blend = |a: f32, b: f32| a + (b - a) * t;
+    let background = Rgba::new(
+        blend(background.r, eval.r),
+        blend(background.g, eval.g),
+        blend(background.b, eval.b),
+        1.0,
+    );
+    format!(
+        "\x1b[48;2;{}m\x1b[38;2;{}m{}\x1b[0m",
+        rgb(background),
+        rgb(palette.text),
+        text
+    )
+}
+
+fn rgb(color: Rgba<f32>) -> String {
+    let channel = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "{};{};{}",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
@@ -0,0 +1,166 @@
+use super::*;
+
+use std::collections::BTreeMap;
+
+/// SVERL-P contributions averaged over the states visited by the policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregateSverl {
+    /// Visitation-weighted mean contribution of each cell.
+    pub mean: Grid<f64>,
+    /// Visitation-weighted mean absolute contribution of each cell.
+    pub mean_abs: Grid<f64>,
+    /// Every state where the policy has to act, most likely first.
+    pub states: Vec<VisitedState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisitedState {
+    #[serde(with = "board_text")]
+    pub board: Grid,
+    /// Probability of visiting the state, normalized over all visited states.
+    pub weight: f64,
+    pub values: Grid<f64>,
+}
+
+/// Computes [AggregateSverl] one state at a time, since explaining all of them takes a while.
+pub struct AggregateProgress {
+    global: bool,
+    gamma: f64,
+    pending: Vec<(Grid, f64)>,
+    states: Vec<VisitedState>,
+}
+
+impl AggregateProgress {
+    pub fn new(
+        global: bool,
+        gamma: f64,
+        policy: &mut Policy,
+        opponent: Option<&mut Policy>,
+    ) -> Self {
+        let distribution = state_distribution(policy, opponent);
+        let total: f64 = distribution.values().sum();
+        let mut pending: Vec<_> = distribution
+            .into_iter()
+            .map(|(board, probability)| (board, probability / total))
+            .collect();
+        // Explain the most likely states first
+        pending.sort_by_key(|&(_, weight)| r64(weight));
+        Self {
+            global,
+            gamma,
+            pending,
+            states: Vec::new(),
+        }
+    }
+
+    /// Explains the next state, returns `false` once there is nothing left.
+    pub fn step(&mut self, policy: &mut Policy, opponent: Option<&mut Policy>) -> bool {
+        let Some((board, weight)) = self.pending.pop() else {
+            return false;
+        };
+        let values = board.sverl(self.global, self.gamma, policy, opponent);
+        self.states.push(VisitedState {
+            board,
+            weight,
+            values,
+        });
+        true
+    }
+
+    pub fn done(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn total(&self) -> usize {
+        self.states.len() + self.pending.len()
+    }
+
+    pub fn finish(self) -> AggregateSverl {
+        AggregateSverl::from_states(self.states)
+    }
+}
+
+impl AggregateSverl {
+    /// Aggregates the explanations of states with normalized weights.
+    pub fn from_states(states: Vec<VisitedState>) -> Self {
+        let mean = Grid::from_fn(|pos| {
+            states
+                .iter()
+                .map(|state| state.weight * state.values.get(pos).unwrap())
+                .sum()
+        });
+        let mean_abs = Grid::from_fn(|pos| {
+            states
+                .iter()
+                .map(|state| state.weight * state.values.get(pos).unwrap().abs())
+                .sum()
+        });
+        Self {
+            mean,
+            mean_abs,
+            states,
+        }
+    }
+
+    /// Contributions of the cell with the weight of their state, sorted by contribution.
+    pub fn distribution(&self, pos: vec2<Coord>) -> Vec<(f64, f64)> {
+        let mut values: Vec<_> = self
+            .states
+            .iter()
+            .map(|state| (*state.values.get(pos).unwrap(), state.weight))
+            .collect();
+        values.sort_by_key(|&(value, _)| r64(value));
+        values
+    }
+
+    /// The weighted `q`-quantile of the contributions of the cell.
+    pub fn quantile(&self, pos: vec2<Coord>, q: f64) -> f64 {
+        let mut acc = 0.0;
+        let distribution = self.distribution(pos);
+        for &(value, weight) in &distribution {
+            acc += weight;
+            if acc >= q {
+                return value;
+            }
+        }
+        distribution.last().map_or(0.0, |&(value, _)| value)
+    }
+}
+
+/// Expected number of visits of each state where the policy acts, against the opponent if given.
+pub fn state_distribution(
+    policy: &mut Policy,
+    mut opponent: Option<&mut Policy>,
+) -> BTreeMap<Grid, f64> {
+    let mut result = BTreeMap::new();
+    for side in [Player::X, Player::O] {
+        let mut layer = BTreeMap::from([(Grid::new(), 1.0)]);
+        while !layer.is_empty() {
+            let mut next = BTreeMap::new();
+            for (grid, probability) in layer {
+                let Some(player) = grid.current_player() else {
+                    continue;
+                };
+
+                let weights = match opponent.as_mut() {
+                    Some(opponent) if player != side => opponent(&grid),
+                    _ => policy(&grid),
+                };
+                for pos in grid.empty_positions() {
+                    let prob = match weights.get(pos) {
+                        Some(&p) if p > 0.0 => p,
+                        _ => continue,
+                    };
+                    let mut grid = grid.clone();
+                    grid.set(pos, player.into());
+                    *next.entry(grid).or_insert(0.0) += probability * prob;
+                }
+                if player == side {
+                    result.insert(grid, probability);
+                }
+            }
+            layer = next;
+        }
+    }
+    result
+}
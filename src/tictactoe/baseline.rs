@@ -0,0 +1,134 @@
+use super::*;
+
+/// Width of the kernel weighting the masks of LIME, the default of LIME for images.
+const LIME_KERNEL_WIDTH: f64 = 0.25;
+/// Strength of the ridge penalty of the LIME surrogate, the default of LIME.
+const LIME_RIDGE: f64 = 1.0;
+/// Refits of the LIME surrogate on resampled observations, estimating its standard error.
+const LIME_BOOTSTRAP: usize = 20;
+
+impl Grid<Tile> {
+    /// The change of the policy when only the feature is hidden.
+    pub fn occlusion(&self, policy: &mut Policy) -> Grid<Grid<f64>> {
+        let full = self.full_observation();
+        let value = full.value(policy);
+        Grid::from_fn(|feature| {
+            let mut occluded = full.clone();
+            let sub = occluded.subtract(feature);
+            assert!(sub, "Full observation does not have the feature");
+            value.clone() - occluded.value(policy)
+        })
+    }
+
+    /// Coefficients of a LIME surrogate of the policy `pi_c`, fitted on random observations,
+    /// and their mean bootstrap standard error.
+    pub fn lime(
+        &self,
+        samples: usize,
+        rng: &mut impl Rng,
+        policy: &mut Policy,
+    ) -> (Grid<Grid<f64>>, f64) {
+        let positions: Vec<_> = self.positions().collect();
+        let n = positions.len();
+        // The explained board is the first sample, as in LIME
+        let full_mask = (1 << n) - 1;
+        let masks = std::iter::once(full_mask)
+            .chain((1..samples).map(|_| rng.gen_range(0..=full_mask)))
+            .collect::<Vec<usize>>();
+
+        let mut values: HashMap<usize, Grid<f64>> = HashMap::new();
+        let observations: Vec<_> = masks
+            .into_iter()
+            .map(|mask| {
+                let value = values
+                    .entry(mask)
+                    .or_insert_with(|| self.mask_observation(mask).value(policy));
+                let distance = 1.0 - (mask.count_ones() as f64 / n as f64).sqrt();
+                let weight = (-(distance / LIME_KERNEL_WIDTH).powi(2)).exp().sqrt();
+                let x: Vec<f64> = (0..n)
+                    .map(|t| ((mask >> t) % 2) as f64)
+                    .chain([1.0])
+                    .collect();
+                (x, weight, value.clone())
+            })
+            .collect();
+        let coefficients = fit(n, &observations);
+
+        let fits: Vec<_> = (0..LIME_BOOTSTRAP)
+            .map(|_| {
+                let resample: Vec<_> = (0..observations.len())
+                    .map(|_| observations[rng.gen_range(0..observations.len())].clone())
+                    .collect();
+                fit(n, &resample)
+            })
+            .collect();
+        let mut variance = 0.0;
+        for t in 0..n {
+            let mut mean = fits
+                .iter()
+                .map(|fit| fit[t].clone())
+                .fold(Grid::zero(), Grid::add);
+            mean *= (LIME_BOOTSTRAP as f64).recip();
+            for fit in &fits {
+                let mut deviation = fit[t].clone() - mean.clone();
+                deviation *= deviation.clone();
+                variance += deviation.sum();
+            }
+        }
+        let actions = Grid::<f64>::zero().positions().count();
+        variance /= ((LIME_BOOTSTRAP - 1) * n.max(1) * actions) as f64;
+
+        let values = Grid::from_fn(|feature| {
+            let t = positions.iter().position(|&pos| pos == feature).unwrap();
+            coefficients[t].clone()
+        });
+        (values, variance.sqrt())
+    }
+}
+
+/// Weighted ridge regression for all actions at once, with the intercept as the last feature.
+fn fit(n: usize, observations: &[(Vec<f64>, f64, Grid<f64>)]) -> Vec<Grid<f64>> {
+    // Normal equations
+    let mut lhs = vec![vec![0.0; n + 1]; n + 1];
+    let mut rhs = vec![Grid::zero(); n + 1];
+    for (x, weight, value) in observations {
+        for i in 0..=n {
+            for j in 0..=n {
+                lhs[i][j] += weight * x[i] * x[j];
+            }
+            let mut term = value.clone();
+            term *= weight * x[i];
+            rhs[i] = rhs[i].clone() + term;
+        }
+    }
+    for (i, row) in lhs.iter_mut().enumerate().take(n) {
+        row[i] += LIME_RIDGE;
+    }
+    solve(lhs, rhs)
+}
+
+/// Gaussian elimination without pivoting, for positive definite matrices.
+fn solve(mut lhs: Vec<Vec<f64>>, mut rhs: Vec<Grid<f64>>) -> Vec<Grid<f64>> {
+    let n = lhs.len();
+    for i in 0..n {
+        let pivot = lhs[i].clone();
+        for j in i + 1..n {
+            let factor = lhs[j][i] / pivot[i];
+            for (target, value) in lhs[j].iter_mut().zip(&pivot).skip(i) {
+                *target -= factor * value;
+            }
+            let mut term = rhs[i].clone();
+            term *= factor;
+            rhs[j] = rhs[j].clone() - term;
+        }
+    }
+    for i in (0..n).rev() {
+        for j in i + 1..n {
+            let mut term = rhs[j].clone();
+            term *= lhs[i][j];
+            rhs[i] = rhs[i].clone() - term;
+        }
+        rhs[i] *= lhs[i][i].recip();
+    }
+    rhs
+}
@@ -0,0 +1,154 @@
+use super::*;
+
+/// A change to a single cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// A piece of the player on an empty cell.
+    Add(Action, Player),
+    /// The piece of the player taken off the board.
+    Remove(Action, Player),
+    /// The piece of the player replaced by one of the other player.
+    Swap(Action, Player),
+}
+
+impl Edit {
+    pub fn position(self) -> Action {
+        match self {
+            Edit::Add(pos, _) | Edit::Remove(pos, _) | Edit::Swap(pos, _) => pos,
+        }
+    }
+
+    /// The piece on the cell after the edit.
+    pub fn tile(self) -> Tile {
+        match self {
+            Edit::Add(_, player) => player.into(),
+            Edit::Remove(..) => Tile::Empty,
+            Edit::Swap(_, player) => player.next().into(),
+        }
+    }
+}
+
+/// Cells are indexed in reading order, like on the command line.
+impl std::fmt::Display for Edit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let index = Grid::new()
+            .reading_order()
+            .position(|pos| pos == self.position())
+            .expect("edit outside the grid");
+        match self {
+            Edit::Add(_, player) => write!(f, "add {:?} at {}", player, index),
+            Edit::Remove(_, player) => write!(f, "remove {:?} at {}", player, index),
+            Edit::Swap(_, player) => {
+                write!(f, "swap {:?} at {} for {:?}", player, index, player.next())
+            }
+        }
+    }
+}
+
+/// A board close to the explained one where the decision is different.
+#[derive(Debug, Clone)]
+pub struct Counterfactual {
+    pub board: Grid,
+    /// The changes from the explained board, in reading order.
+    pub edits: Vec<Edit>,
+}
+
+impl Grid<Tile> {
+    /// The actions the policy is most likely to take, in reading order.
+    pub fn top_actions(&self, policy: &mut Policy) -> Vec<Action> {
+        let probabilities = policy(self);
+        let max = self
+            .empty_positions()
+            .map(|pos| r64(*probabilities.get(pos).unwrap()))
+            .max()
+            .map_or(0.0, |max| max.raw());
+        self.reading_order()
+            .filter(|&pos| {
+                let probability = *probabilities.get(pos).unwrap();
+                self.check(pos) && probability > 0.0 && probability >= max - 1e-9
+            })
+            .collect()
+    }
+
+    /// The closest boards where none of the actions the policy is most likely to take remain so.
+    pub fn top_action_counterfactuals(&self, policy: &mut Policy) -> Vec<Counterfactual> {
+        let top = self.top_actions(policy);
+        self.counterfactuals(|board| {
+            board
+                .top_actions(policy)
+                .iter()
+                .all(|action| !top.contains(action))
+        })
+    }
+
+    /// The closest boards where the minimax search expects another outcome.
+    pub fn minimax_counterfactuals(
+        &self,
+        cache: &mut MinimaxCache,
+        limit: Option<usize>,
+    ) -> Vec<Counterfactual> {
+        let Some(player) = self.current_player() else {
+            return Vec::new();
+        };
+        let mut outcome =
+            |board: &Grid| minimax_value(board, cache, player, limit).partial_cmp(&0.0);
+        let expected = outcome(self);
+        self.counterfactuals(|board| outcome(board) != expected)
+    }
+
+    /// The boards with the fewest edits where `changed` holds, with the same player to move.
+    fn counterfactuals(&self, mut changed: impl FnMut(&Grid) -> bool) -> Vec<Counterfactual> {
+        let Some(player) = self.current_player() else {
+            return Vec::new();
+        };
+
+        let hidden: Vec<_> = self.positions().collect();
+        let mut candidates: Vec<_> = self
+            .partial_observation(&hidden)
+            .possible_states()
+            .into_iter()
+            .filter(|board| board.current_player() == Some(player))
+            .map(|board| Counterfactual {
+                edits: self.edits(&board),
+                board,
+            })
+            .filter(|counterfactual| !counterfactual.edits.is_empty())
+            .collect();
+        candidates.sort_by_key(|counterfactual| counterfactual.edits.len());
+
+        let mut result: Vec<Counterfactual> = Vec::new();
+        for counterfactual in candidates {
+            if result
+                .first()
+                .is_some_and(|first| first.edits.len() < counterfactual.edits.len())
+            {
+                break;
+            }
+            if changed(&counterfactual.board) {
+                result.push(counterfactual);
+            }
+        }
+        result
+    }
+
+    /// The changes turning this board into the other one.
+    fn edits(&self, other: &Grid) -> Vec<Edit> {
+        let player = |tile: &Tile| match tile {
+            Tile::Empty => None,
+            Tile::X => Some(Player::X),
+            Tile::O => Some(Player::O),
+        };
+        self.reading_order()
+            .filter_map(|pos| {
+                let before = player(self.get(pos).unwrap());
+                let after = player(other.get(pos).unwrap());
+                match (before, after) {
+                    (None, Some(after)) => Some(Edit::Add(pos, after)),
+                    (Some(before), None) => Some(Edit::Remove(pos, before)),
+                    (Some(before), Some(after)) if before != after => Some(Edit::Swap(pos, before)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
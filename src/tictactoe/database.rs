@@ -0,0 +1,76 @@
+use super::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Version of the database format, to be increased whenever the entries change.
+pub const DATABASE_VERSION: u32 = 3;
+
+/// Precomputed explanations of every reachable state for one policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Database {
+    /// Databases written before versioning have version 0.
+    #[serde(default)]
+    pub version: u32,
+    /// Name of the policy, as accepted by the command line.
+    pub policy: String,
+    pub gamma: f64,
+    pub minimax_depth: Option<usize>,
+    /// Explanations by board in its text format.
+    pub entries: BTreeMap<String, DatabaseEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseEntry {
+    pub shapley: Explanation<Grid<Grid<f64>>>,
+    pub sverl_local: Explanation<Grid<f64>>,
+    pub sverl_global: Explanation<Grid<f64>>,
+}
+
+impl Database {
+    /// Checks that the database was written in the current format.
+    pub fn check(&self) -> Result<(), String> {
+        if self.version != DATABASE_VERSION {
+            return Err(format!(
+                "database version {} does not match the expected version {}",
+                self.version, DATABASE_VERSION
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, board: &Grid) -> Option<&DatabaseEntry> {
+        self.entries.get(&board.to_string())
+    }
+}
+
+impl DatabaseEntry {
+    pub fn new(board: &Grid, parameters: &ExplanationParameters, policy: &mut Policy) -> Self {
+        Self {
+            shapley: board.explain_policy(parameters, policy),
+            sverl_local: board.explain_sverl(false, parameters, policy, None),
+            sverl_global: board.explain_sverl(true, parameters, policy, None),
+        }
+    }
+}
+
+/// All states that can occur in a game, including finished ones.
+pub fn reachable_states() -> Vec<Grid> {
+    let mut result = Vec::new();
+    let mut layer = BTreeSet::from([Grid::new()]);
+    while !layer.is_empty() {
+        let mut next = BTreeSet::new();
+        for grid in &layer {
+            let Some(player) = grid.current_player() else {
+                continue;
+            };
+            for pos in grid.empty_positions() {
+                let mut grid = grid.clone();
+                grid.set(pos, player.into());
+                next.insert(grid);
+            }
+        }
+        result.extend(layer);
+        layer = next;
+    }
+    result
+}
@@ -0,0 +1,481 @@
+use super::{shapley::Observation, *};
+
+use std::collections::BTreeMap;
+
+/// Largest efficiency residual not warned about, leaving room for rounding errors.
+const EFFICIENCY_TOLERANCE: f64 = 1e-9;
+
+/// The methods attributing to cells, and the policy under partial observation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExplanationMethod {
+    /// Semivalues of the policy, with the weighting of the parameters.
+    Shapley,
+    SverlLocal,
+    SverlGlobal,
+    StateValue,
+    /// Change of the policy when a single cell is hidden, a baseline.
+    Occlusion,
+    /// Coefficients of a LIME surrogate of the policy, a baseline.
+    Lime,
+    /// Owen values of the policy, for the groups of the parameters.
+    Owen,
+    /// Owen values of SVERL-P local.
+    OwenSverl,
+    /// Shapley interaction indices of pairs of cells in the policy.
+    Interaction,
+    /// Shapley interaction indices of pairs of cells in SVERL-P local.
+    InteractionSverl,
+    /// The policy with the cells of the parameters hidden, not an attribution.
+    Coalition,
+}
+
+impl std::fmt::Display for ExplanationMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExplanationMethod::Shapley => write!(f, "shapley"),
+            ExplanationMethod::SverlLocal => write!(f, "sverl-local"),
+            ExplanationMethod::SverlGlobal => write!(f, "sverl-global"),
+            ExplanationMethod::StateValue => write!(f, "state-value"),
+            ExplanationMethod::Occlusion => write!(f, "occlusion"),
+            ExplanationMethod::Lime => write!(f, "lime"),
+            ExplanationMethod::Owen => write!(f, "owen"),
+            ExplanationMethod::OwenSverl => write!(f, "owen-sverl"),
+            ExplanationMethod::Interaction => write!(f, "interaction"),
+            ExplanationMethod::InteractionSverl => write!(f, "interaction-sverl"),
+            ExplanationMethod::Coalition => write!(f, "coalition"),
+        }
+    }
+}
+
+/// What an explanation was computed with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExplanationParameters {
+    /// Name of the policy, as accepted by the command line.
+    pub policy: String,
+    /// Depth limit of the minimax search, shared with the opponent.
+    pub minimax_depth: Option<usize>,
+    /// Name of the policy of the other player in SVERL-P rollouts, `None` for self-play.
+    #[serde(default)]
+    pub opponent: Option<String>,
+    pub weighting: Semivalue,
+    /// Discount factor, unused by [ExplanationMethod::Shapley].
+    pub gamma: f64,
+    /// How the observations of [ExplanationMethod::Lime] were drawn, `None` for the other methods.
+    #[serde(default)]
+    pub lime: Option<LimeSampling>,
+    /// Groups of cells of the Owen values, `None` for the other methods.
+    #[serde(default)]
+    pub partition: Option<Partition>,
+    /// Cells hidden by [ExplanationMethod::Coalition], `None` for the other methods.
+    #[serde(default)]
+    pub hidden: Option<Vec<Action>>,
+}
+
+/// The random observations a LIME surrogate is fitted on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LimeSampling {
+    pub samples: usize,
+    pub seed: u64,
+}
+
+/// The values of an explanation, together with what they explain and how.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Explanation<T> {
+    #[serde(with = "board_text")]
+    pub board: Grid,
+    /// The player to act, `None` if the game is over.
+    pub player: Option<Player>,
+    pub method: ExplanationMethod,
+    pub parameters: ExplanationParameters,
+    pub values: T,
+    /// `v(N)` of each action, the one of SVERL-P local for SVERL-P global.
+    pub full: Grid<f64>,
+    /// `v(∅)` of each action.
+    pub empty: Grid<f64>,
+    /// How far the values are from adding up to `v(N) − v(∅)`, the largest over the actions.
+    pub efficiency_residual: f64,
+    /// Time taken to compute the values, in seconds.
+    pub time: f64,
+    /// Standard error of estimated values, `None` when they are computed exactly.
+    pub estimator_error: Option<f64>,
+}
+
+impl<T> Explanation<T> {
+    /// Whether the values should add up to `v(N) − v(∅)`.
+    pub fn is_efficient(&self) -> bool {
+        match self.method {
+            ExplanationMethod::Shapley
+            | ExplanationMethod::SverlLocal
+            | ExplanationMethod::StateValue => self.parameters.weighting == Semivalue::Shapley,
+            ExplanationMethod::Owen
+            | ExplanationMethod::OwenSverl
+            | ExplanationMethod::Interaction
+            | ExplanationMethod::InteractionSverl => true,
+            ExplanationMethod::SverlGlobal
+            | ExplanationMethod::Occlusion
+            | ExplanationMethod::Lime
+            | ExplanationMethod::Coalition => false,
+        }
+    }
+
+    /// Logs the efficiency residual, warning if it does not vanish, and panicking in tests.
+    fn check_efficiency(self) -> Self {
+        log::debug!(
+            "{} efficiency residual on {}: {:e}",
+            self.method,
+            self.board,
+            self.efficiency_residual
+        );
+        if self.is_efficient() && self.efficiency_residual.abs() >= EFFICIENCY_TOLERANCE {
+            let message = format!(
+                "{} values on {} do not add up to v(N) - v(empty), residual {:e}",
+                self.method, self.board, self.efficiency_residual
+            );
+            if cfg!(test) {
+                panic!("{}", message);
+            }
+            log::warn!("{}", message);
+        }
+        self
+    }
+}
+
+/// Values that are supposed to add up to the difference of the characteristic function.
+trait Efficiency {
+    fn efficiency_residual(&self, full: &Grid<f64>, empty: &Grid<f64>) -> f64;
+}
+
+impl Efficiency for Grid<Grid<f64>> {
+    fn efficiency_residual(&self, full: &Grid<f64>, empty: &Grid<f64>) -> f64 {
+        action_residual(
+            self.positions().map(|feature| self.get(feature).unwrap()),
+            full,
+            empty,
+        )
+    }
+}
+
+impl Efficiency for Grid<f64> {
+    fn efficiency_residual(&self, full: &Grid<f64>, empty: &Grid<f64>) -> f64 {
+        self.sum() - (full.sum() - empty.sum())
+    }
+}
+
+impl Efficiency for Grid<Grid<Grid<f64>>> {
+    /// Only the Shapley values on the diagonal add up.
+    fn efficiency_residual(&self, full: &Grid<f64>, empty: &Grid<f64>) -> f64 {
+        let diagonal = self
+            .positions()
+            .map(|feature| self.get(feature).unwrap().get(feature).unwrap());
+        action_residual(diagonal, full, empty)
+    }
+}
+
+impl Efficiency for Owen<Grid<f64>> {
+    fn efficiency_residual(&self, full: &Grid<f64>, empty: &Grid<f64>) -> f64 {
+        let cells = self.cells.efficiency_residual(full, empty);
+        let groups = action_residual(self.groups.iter(), full, empty);
+        largest(cells, groups)
+    }
+}
+
+impl Efficiency for Owen<f64> {
+    fn efficiency_residual(&self, full: &Grid<f64>, empty: &Grid<f64>) -> f64 {
+        let cells = self.cells.efficiency_residual(full, empty);
+        let groups = self.groups.iter().sum::<f64>() - (full.sum() - empty.sum());
+        largest(cells, groups)
+    }
+}
+
+/// The residual of values for each action, the largest over the actions.
+fn action_residual<'a>(
+    values: impl Iterator<Item = &'a Grid<f64>>,
+    full: &Grid<f64>,
+    empty: &Grid<f64>,
+) -> f64 {
+    let sum = values.fold(Grid::zero(), |sum, value| sum + value.clone());
+    let residual = sum - (full.clone() - empty.clone());
+    residual
+        .positions()
+        .map(|action| *residual.get(action).unwrap())
+        .fold(0.0, largest)
+}
+
+fn largest(a: f64, b: f64) -> f64 {
+    if b.abs() > a.abs() {
+        b
+    } else {
+        a
+    }
+}
+
+impl Grid<Tile> {
+    /// Semivalues of the policy.
+    pub fn explain_policy(
+        &self,
+        parameters: &ExplanationParameters,
+        policy: &mut Policy,
+    ) -> Explanation<Grid<Grid<f64>>> {
+        let timer = Timer::new();
+        let values = self.semivalue(parameters.weighting, policy);
+        let time = timer.elapsed().as_secs_f64();
+        self.policy_explanation(
+            ExplanationMethod::Shapley,
+            parameters.clone(),
+            values,
+            time,
+            policy,
+        )
+    }
+
+    /// Occlusion of single cells, a baseline for the semivalues of the policy.
+    pub fn explain_occlusion(
+        &self,
+        parameters: &ExplanationParameters,
+        policy: &mut Policy,
+    ) -> Explanation<Grid<Grid<f64>>> {
+        let timer = Timer::new();
+        let values = self.occlusion(policy);
+        let time = timer.elapsed().as_secs_f64();
+        self.policy_explanation(
+            ExplanationMethod::Occlusion,
+            parameters.clone(),
+            values,
+            time,
+            policy,
+        )
+    }
+
+    /// A LIME surrogate, a baseline for the semivalues of the policy.
+    pub fn explain_lime(
+        &self,
+        parameters: &ExplanationParameters,
+        sampling: LimeSampling,
+        policy: &mut Policy,
+    ) -> Explanation<Grid<Grid<f64>>> {
+        let timer = Timer::new();
+        let (_, mut rng) = seeded_rng(Some(sampling.seed));
+        let (values, error) = self.lime(sampling.samples, &mut rng, policy);
+        let time = timer.elapsed().as_secs_f64();
+        let parameters = ExplanationParameters {
+            lime: Some(sampling),
+            ..parameters.clone()
+        };
+        Explanation {
+            estimator_error: Some(error),
+            ..self.policy_explanation(ExplanationMethod::Lime, parameters, values, time, policy)
+        }
+    }
+
+    /// Owen values of the policy, for the groups of cells of the partition.
+    pub fn explain_owen(
+        &self,
+        parameters: &ExplanationParameters,
+        partition: &Partition,
+        policy: &mut Policy,
+    ) -> Explanation<Owen<Grid<f64>>> {
+        let timer = Timer::new();
+        let values = self.owen(partition, policy);
+        let time = timer.elapsed().as_secs_f64();
+        let parameters = ExplanationParameters {
+            partition: Some(partition.clone()),
+            ..parameters.clone()
+        };
+        self.policy_explanation(ExplanationMethod::Owen, parameters, values, time, policy)
+    }
+
+    /// Shapley interactions of pairs of cells in the policy.
+    pub fn explain_interaction(
+        &self,
+        parameters: &ExplanationParameters,
+        policy: &mut Policy,
+    ) -> Explanation<Grid<Grid<Grid<f64>>>> {
+        let timer = Timer::new();
+        let values = self.shapley_interaction(policy);
+        let time = timer.elapsed().as_secs_f64();
+        let method = ExplanationMethod::Interaction;
+        self.policy_explanation(method, parameters.clone(), values, time, policy)
+    }
+
+    /// The policy with the cells hidden, next to the one with every cell observed.
+    pub fn explain_coalition(
+        &self,
+        parameters: &ExplanationParameters,
+        hidden: &[Action],
+        policy: &mut Policy,
+    ) -> Explanation<Grid<f64>> {
+        let timer = Timer::new();
+        let values = self.partial_observation(hidden).value(policy);
+        let time = timer.elapsed().as_secs_f64();
+        let parameters = ExplanationParameters {
+            hidden: Some(hidden.to_vec()),
+            ..parameters.clone()
+        };
+        self.policy_explanation(
+            ExplanationMethod::Coalition,
+            parameters,
+            values,
+            time,
+            policy,
+        )
+    }
+
+    /// Completes the explanation of the policy with its values under full and empty observation.
+    fn policy_explanation<T: Efficiency>(
+        &self,
+        method: ExplanationMethod,
+        parameters: ExplanationParameters,
+        values: T,
+        time: f64,
+        policy: &mut Policy,
+    ) -> Explanation<T> {
+        let hidden: Vec<_> = self.positions().collect();
+        let full = self.full_observation().value(policy);
+        let empty = self.partial_observation(&hidden).value(policy);
+        Explanation {
+            board: self.clone(),
+            player: self.current_player(),
+            method,
+            parameters,
+            efficiency_residual: values.efficiency_residual(&full, &empty),
+            values,
+            full,
+            empty,
+            time,
+            estimator_error: None,
+        }
+        .check_efficiency()
+    }
+
+    /// SVERL-P local or global, against the opponent described by the parameters.
+    pub fn explain_sverl(
+        &self,
+        global: bool,
+        parameters: &ExplanationParameters,
+        policy: &mut Policy,
+        mut opponent: Option<&mut Policy>,
+    ) -> Explanation<Grid<f64>> {
+        let timer = Timer::new();
+        let values = self.sverl_semivalue(
+            parameters.weighting,
+            global,
+            parameters.gamma,
+            policy,
+            opponent.as_deref_mut(),
+        );
+        let time = timer.elapsed().as_secs_f64();
+        let method = if global {
+            ExplanationMethod::SverlGlobal
+        } else {
+            ExplanationMethod::SverlLocal
+        };
+        self.sverl_explanation(method, parameters.clone(), values, time, policy, opponent)
+    }
+
+    /// Owen values of SVERL-P local, for the groups of cells of the partition.
+    pub fn explain_sverl_owen(
+        &self,
+        parameters: &ExplanationParameters,
+        partition: &Partition,
+        policy: &mut Policy,
+        mut opponent: Option<&mut Policy>,
+    ) -> Explanation<Owen<f64>> {
+        let timer = Timer::new();
+        let values = self.sverl_owen(partition, parameters.gamma, policy, opponent.as_deref_mut());
+        let time = timer.elapsed().as_secs_f64();
+        let parameters = ExplanationParameters {
+            partition: Some(partition.clone()),
+            ..parameters.clone()
+        };
+        let method = ExplanationMethod::OwenSverl;
+        self.sverl_explanation(method, parameters, values, time, policy, opponent)
+    }
+
+    /// Shapley interactions of pairs of cells in SVERL-P local.
+    pub fn explain_sverl_interaction(
+        &self,
+        parameters: &ExplanationParameters,
+        policy: &mut Policy,
+        mut opponent: Option<&mut Policy>,
+    ) -> Explanation<Grid<Grid<Grid<f64>>>> {
+        let timer = Timer::new();
+        let values = self.sverl_interaction(parameters.gamma, policy, opponent.as_deref_mut());
+        let time = timer.elapsed().as_secs_f64();
+        let method = ExplanationMethod::InteractionSverl;
+        self.sverl_explanation(method, parameters.clone(), values, time, policy, opponent)
+    }
+
+    /// Completes the explanation with the local characteristic function of SVERL-P.
+    fn sverl_explanation<T: Efficiency>(
+        &self,
+        method: ExplanationMethod,
+        parameters: ExplanationParameters,
+        values: T,
+        time: f64,
+        policy: &mut Policy,
+        mut opponent: Option<&mut Policy>,
+    ) -> Explanation<T> {
+        let mut cache = BTreeMap::new();
+        let mut value = |observation| match self.current_player() {
+            Some(player) => self.sverl_local_value(
+                player,
+                &observation,
+                &mut cache,
+                parameters.gamma,
+                policy,
+                opponent.as_deref_mut(),
+            ),
+            None => Grid::zero(),
+        };
+        let hidden: Vec<_> = self.positions().collect();
+        let full = value(self.full_observation());
+        let empty = value(self.partial_observation(&hidden));
+        Explanation {
+            board: self.clone(),
+            player: self.current_player(),
+            method,
+            parameters,
+            efficiency_residual: values.efficiency_residual(&full, &empty),
+            values,
+            full,
+            empty,
+            time,
+            estimator_error: None,
+        }
+        .check_efficiency()
+    }
+
+    /// Attribution of the state value of the policy.
+    pub fn explain_state_value(
+        &self,
+        parameters: &ExplanationParameters,
+        policy: &mut Policy,
+    ) -> Explanation<Grid<f64>> {
+        let timer = Timer::new();
+        let values = self.sverl_state_value(parameters.weighting, parameters.gamma, policy);
+        let time = timer.elapsed().as_secs_f64();
+
+        let mut cache = BTreeMap::new();
+        let mut value = |observation: Observation| match self.current_player() {
+            Some(player) => observation.state_value(&mut cache, parameters.gamma, player, policy),
+            None => Grid::zero(),
+        };
+        let hidden: Vec<_> = self.positions().collect();
+        let full = value(self.full_observation());
+        let empty = value(self.partial_observation(&hidden));
+        Explanation {
+            board: self.clone(),
+            player: self.current_player(),
+            method: ExplanationMethod::StateValue,
+            parameters: parameters.clone(),
+            efficiency_residual: values.efficiency_residual(&full, &empty),
+            values,
+            full,
+            empty,
+            time,
+            estimator_error: None,
+        }
+        .check_efficiency()
+    }
+}
@@ -0,0 +1,161 @@
+use super::*;
+
+/// A game as saved to files, which hold a list of games.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    #[serde(with = "board_text")]
+    pub opening: Grid,
+    pub moves: Vec<Action>,
+    pub winner: Option<Player>,
+    pub x: PlayerRecord,
+    pub o: PlayerRecord,
+    /// Seed of the random number generator used for the moves of the AI.
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub explanations: Vec<PlyExplanation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    /// `human`, or the policy as named on the command line.
+    pub policy: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimax_depth: Option<usize>,
+}
+
+/// The explanation shown before a move.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlyExplanation {
+    /// Number of moves made before.
+    pub ply: usize,
+    pub explanation: Explanation<Grid<f64>>,
+}
+
+impl GameRecord {
+    pub fn new(
+        opening: Grid,
+        moves: Vec<Action>,
+        x: PlayerRecord,
+        o: PlayerRecord,
+        seed: Option<u64>,
+    ) -> Self {
+        let mut record = Self {
+            opening,
+            moves,
+            winner: None,
+            x,
+            o,
+            seed,
+            explanations: Vec::new(),
+        };
+        record.winner = record.board(record.moves.len()).winner();
+        record
+    }
+
+    /// Checks the opening, the moves and the recorded winner.
+    pub fn validate(&self) -> Result<(), String> {
+        self.opening
+            .validate()
+            .map_err(|err| format!("invalid opening: {}", err))?;
+        let mut grid = self.opening.clone();
+        for (ply, &action) in self.moves.iter().enumerate() {
+            let player = grid
+                .current_player()
+                .ok_or_else(|| format!("move {} is made after the game is over", ply))?;
+            if !grid.check(action) {
+                return Err(format!("move {} is not on an empty cell", ply));
+            }
+            grid.set(action, player.into());
+        }
+        if grid.winner() != self.winner {
+            return Err(format!(
+                "recorded winner {:?} does not match the board, where {:?} won",
+                self.winner,
+                grid.winner()
+            ));
+        }
+        if let Some(explanation) = self
+            .explanations
+            .iter()
+            .find(|explanation| explanation.ply > self.moves.len())
+        {
+            return Err(format!(
+                "explanation before move {} of a game of {} moves",
+                explanation.ply,
+                self.moves.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The board after the first `ply` moves.
+    pub fn board(&self, ply: usize) -> Grid {
+        let mut grid = self.opening.clone();
+        for &action in &self.moves[..ply.min(self.moves.len())] {
+            if let Some(player) = grid.current_player() {
+                grid.set(action, player.into());
+            }
+        }
+        grid
+    }
+}
+
+/// Plays from the opening until the game is finished, sampling the moves from the policies.
+pub fn play_game(
+    opening: &Grid,
+    x: &mut Policy,
+    o: &mut Policy,
+    rng: &mut impl Rng,
+) -> Vec<Action> {
+    let mut grid = opening.clone();
+    let mut moves = Vec::new();
+    while let Some(player) = grid.current_player() {
+        let probs = match player {
+            Player::X => x(&grid),
+            Player::O => o(&grid),
+        };
+        let action = choose_action(probs, rng);
+        assert!(grid.check(action), "the policy chose an occupied cell");
+        grid.set(action, player.into());
+        moves.push(action);
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(moves: &[usize]) -> GameRecord {
+        let positions: Vec<_> = Grid::new().reading_order().collect();
+        let player = || PlayerRecord {
+            policy: "human".to_string(),
+            minimax_depth: None,
+        };
+        GameRecord::new(
+            Grid::new(),
+            moves.iter().map(|&i| positions[i]).collect(),
+            player(),
+            player(),
+            None,
+        )
+    }
+
+    #[test]
+    fn validate_accepts_played_games() {
+        let record = record(&[0, 4, 1, 3, 2]);
+        assert_eq!(record.winner, Some(Player::X));
+        assert_eq!(record.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_illegal_moves() {
+        // Onto an occupied cell
+        assert!(record(&[0, 0]).validate().is_err());
+        // After X has won
+        assert!(record(&[0, 4, 1, 3, 2, 5]).validate().is_err());
+        let mut record = record(&[0, 4]);
+        record.winner = Some(Player::O);
+        assert!(record.validate().is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use super::{
+    shapley::{factorial, Observation},
+    *,
+};
+
+use std::collections::BTreeMap;
+
+impl Grid<Tile> {
+    /// Pairwise Shapley interaction indices of the policy.
+    pub fn shapley_interaction(&self, policy: &mut Policy) -> Grid<Grid<Grid<f64>>> {
+        self.interaction_with_value(|observation| observation.value(policy))
+    }
+
+    /// Pairwise interaction indices of SVERL-P local, rolled out against the opponent if given.
+    pub fn sverl_interaction(
+        &self,
+        gamma: f64,
+        policy: &mut Policy,
+        mut opponent: Option<&mut Policy>,
+    ) -> Grid<Grid<Grid<f64>>> {
+        let Some(player) = self.current_player() else {
+            return Grid::from_fn(|_| Grid::from_fn(|_| Grid::zero()));
+        };
+
+        let mut cache = BTreeMap::new();
+        self.interaction_with_value(|observation| {
+            let opponent = opponent.as_deref_mut();
+            self.sverl_local_value(player, observation, &mut cache, gamma, policy, opponent)
+        })
+    }
+
+    /// Computes the Shapley interaction index (Grabisch, 1997) for every pair of features:
+    ///
+    /// `I(i, j) = sum over S in N \ {i, j} of |S|! (n - |S| - 2)! / (n - 1)! *
+    /// (v(S + i + j) - v(S + i) - v(S + j) + v(S))`
+    ///
+    /// The diagonal holds the index of single features, which is their Shapley value.
+    pub fn interaction_with_value(
+        &self,
+        mut value: impl FnMut(&Observation) -> Grid<f64>,
+    ) -> Grid<Grid<Grid<f64>>> {
+        let positions: Vec<_> = self.positions().collect();
+        let n = positions.len();
+        let scale = (factorial(n - 1) as f64).recip();
+
+        // The characteristic function of every coalition, indexed by its bitmask
+        let values: Vec<Grid<f64>> = (0..1usize << n)
+            .map(|mask| value(&self.mask_observation(mask)))
+            .collect();
+
+        let index = |pos: vec2<Coord>| positions.iter().position(|&p| p == pos).unwrap();
+        Grid::from_fn(|a| {
+            Grid::from_fn(|b| {
+                let (i, j) = (index(a), index(b));
+                if i == j {
+                    return (0..1usize << n)
+                        .filter(|&mask| (mask >> i) % 2 == 0)
+                        .map(|mask| {
+                            let s = mask.count_ones() as usize;
+                            let mut value = values[mask | 1 << i].clone() - values[mask].clone();
+                            value *= factorial(s) as f64 * factorial(n - s - 1) as f64
+                                / factorial(n) as f64;
+                            value
+                        })
+                        .fold(Grid::zero(), Grid::add);
+                }
+
+                let mut result = (0..1usize << n)
+                    .filter(|&mask| (mask >> i) % 2 == 0 && (mask >> j) % 2 == 0)
+                    .map(|mask| {
+                        let s = mask.count_ones() as usize;
+                        let mut value = values[mask | 1 << i | 1 << j].clone()
+                            - values[mask | 1 << i].clone()
+                            - values[mask | 1 << j].clone()
+                            + values[mask].clone();
+                        value *= factorial(s) as f64 * factorial(n - s - 2) as f64;
+                        value
+                    })
+                    .fold(Grid::zero(), Grid::add);
+                result *= scale;
+                result
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interaction_of_single_cells_is_shapley() {
+        let board: Grid = "x../.o./...".parse().unwrap();
+        let mut cache = MinimaxCache::new();
+        let mut policy = policy_minimax_cached(None, &mut cache);
+        let interaction = board.shapley_interaction(&mut policy);
+        let shapley = board.shapley(&mut policy);
+        for pos in board.positions() {
+            let single = interaction.get(pos).unwrap().get(pos).unwrap();
+            let shapley = shapley.get(pos).unwrap();
+            for action in board.positions() {
+                let (single, shapley) = (single.get(action).unwrap(), shapley.get(action).unwrap());
+                assert!(
+                    (single - shapley).abs() < 1e-9,
+                    "cell {:?}, action {:?}: interaction {}, shapley {}",
+                    pos,
+                    action,
+                    single,
+                    shapley
+                );
+            }
+        }
+    }
+}
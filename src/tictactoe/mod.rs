@@ -1,8 +1,23 @@
+mod aggregate;
+mod baseline;
+mod counterfactual;
+mod database;
+mod explanation;
+mod game;
+mod interaction;
+mod owen;
 mod policy;
 mod shapley;
 mod sverl;
 
+pub use self::aggregate::*;
+pub use self::counterfactual::*;
+pub use self::database::*;
+pub use self::explanation::*;
+pub use self::game::*;
+pub use self::owen::*;
 pub use self::policy::*;
+pub use self::shapley::Semivalue;
 
 use geng::prelude::*;
 
@@ -13,7 +28,7 @@ pub enum Tile {
     O,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Player {
     X,
     O,
@@ -39,7 +54,8 @@ impl From<Player> for Tile {
 
 pub type Coord = usize;
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Grid<T = Tile> {
     pub cells: [[T; 3]; 3],
 }
@@ -74,6 +90,14 @@ impl<T> Grid<T> {
             .get(position.y)
             .and_then(|row| row.get(position.x))
     }
+
+    /// Positions in the order the grid is read when displayed: rows from the top down.
+    pub fn reading_order(&self) -> impl Iterator<Item = vec2<Coord>> + '_ {
+        let bounds = self.bounds();
+        (bounds.min.y..bounds.max.y)
+            .rev()
+            .flat_map(move |y| (bounds.min.x..bounds.max.x).map(move |x| vec2(x, y)))
+    }
 }
 
 impl Grid<Tile> {
@@ -85,7 +109,7 @@ impl Grid<Tile> {
 
     pub fn check(&self, pos: vec2<Coord>) -> bool {
         self.get(pos)
-            .map_or(false, |tile| matches!(tile, Tile::Empty))
+            .is_some_and(|tile| matches!(tile, Tile::Empty))
     }
 
     pub fn empty_positions(&self) -> impl Iterator<Item = vec2<Coord>> + '_ {
@@ -93,21 +117,16 @@ impl Grid<Tile> {
             .filter(|&pos| matches!(self.get(pos), Some(Tile::Empty)))
     }
 
+    /// The player to move, `None` if the game is over or the board can not be reached.
     pub fn current_player(&self) -> Option<Player> {
-        if self.winner().is_some() || self.empty_positions().next().is_none() {
+        if self.validate().is_err()
+            || self.winner().is_some()
+            || self.empty_positions().next().is_none()
+        {
             return None;
         }
 
-        let mut count_x = 0;
-        let mut count_o = 0;
-        for pos in self.positions() {
-            match self.get(pos) {
-                Some(Tile::X) => count_x += 1,
-                Some(Tile::O) => count_o += 1,
-                _ => {}
-            }
-        }
-
+        let (count_x, count_o) = self.counts();
         if count_x > count_o {
             Some(Player::O)
         } else {
@@ -115,66 +134,48 @@ impl Grid<Tile> {
         }
     }
 
+    /// The player with three in a row, `None` if there is none or both players have one.
     pub fn winner(&self) -> Option<Player> {
-        // horizontal
-        for row in &self.cells {
-            if *row == [Tile::X; 3] {
-                return Some(Player::X);
-            }
-            if *row == [Tile::O; 3] {
-                return Some(Player::O);
-            }
+        match (self.has_line(Player::X), self.has_line(Player::O)) {
+            (true, false) => Some(Player::X),
+            (false, true) => Some(Player::O),
+            _ => None,
         }
+    }
 
-        // vertical
-        for x in self.bounds().min.x..self.bounds().max.x {
-            let mut winner_x = true;
-            let mut winner_o = true;
-            for y in self.bounds().min.y..self.bounds().max.y {
-                if self.get(vec2(x, y)) != Some(&Tile::X) {
-                    winner_x = false;
-                }
-                if self.get(vec2(x, y)) != Some(&Tile::O) {
-                    winner_o = false;
-                }
-            }
-            if winner_x {
-                return Some(Player::X);
-            }
-            if winner_o {
-                return Some(Player::O);
-            }
+    /// Checks that the board can be reached by playing from the empty one, X first.
+    pub fn validate(&self) -> Result<(), BoardError> {
+        let (count_x, count_o) = self.counts();
+        if count_x > count_o + 1 {
+            return Err(BoardError::TooManyX);
         }
-
-        // diagonal
-        let mut winner_main_x = true;
-        let mut winner_main_o = true;
-        let mut winner_sec_x = true;
-        let mut winner_sec_o = true;
-        for x in self.bounds().min.x..self.bounds().max.x {
-            if self.get(vec2(x, x)) != Some(&Tile::X) {
-                winner_main_x = false;
-            }
-            if self.get(vec2(x, x)) != Some(&Tile::O) {
-                winner_main_o = false;
-            }
-
-            let y = self.bounds().max.y.saturating_sub(x).saturating_sub(1);
-            if self.get(vec2(x, y)) != Some(&Tile::X) {
-                winner_sec_x = false;
-            }
-            if self.get(vec2(x, y)) != Some(&Tile::O) {
-                winner_sec_o = false;
-            }
+        if count_o > count_x {
+            return Err(BoardError::TooManyO);
         }
-        if winner_main_x || winner_sec_x {
-            return Some(Player::X);
-        }
-        if winner_main_o || winner_sec_o {
-            return Some(Player::O);
+        match (self.has_line(Player::X), self.has_line(Player::O)) {
+            (true, true) => Err(BoardError::BothWin),
+            // The winner made the last move
+            (true, false) if count_x == count_o => Err(BoardError::PlayAfterWin(Player::X)),
+            (false, true) if count_x > count_o => Err(BoardError::PlayAfterWin(Player::O)),
+            _ => Ok(()),
         }
+    }
 
-        None
+    fn counts(&self) -> (usize, usize) {
+        let count = |tile| self.cells.iter().flatten().filter(|&&t| t == tile).count();
+        (count(Tile::X), count(Tile::O))
+    }
+
+    fn has_line(&self, player: Player) -> bool {
+        let tile = Tile::from(player);
+        let line = |cells: [(Coord, Coord); 3]| {
+            cells
+                .iter()
+                .all(|&(x, y)| self.get(vec2(x, y)) == Some(&tile))
+        };
+        (0..3).any(|i| line([(0, i), (1, i), (2, i)]) || line([(i, 0), (i, 1), (i, 2)]))
+            || line([(0, 0), (1, 1), (2, 2)])
+            || line([(0, 2), (1, 1), (2, 0)])
     }
 
     pub fn reward(&self, player: Player) -> f64 {
@@ -191,6 +192,101 @@ impl Grid<Tile> {
     }
 }
 
+/// Why a board can not be reached in a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardError {
+    /// X has more than one piece more than O.
+    TooManyX,
+    /// O has more pieces than X.
+    TooManyO,
+    /// Both players have three in a row.
+    BothWin,
+    /// The other player moved after this one won.
+    PlayAfterWin(Player),
+}
+
+impl std::fmt::Display for BoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardError::TooManyX => write!(f, "X has too many pieces"),
+            BoardError::TooManyO => write!(f, "O has too many pieces"),
+            BoardError::BothWin => write!(f, "both players have three in a row"),
+            BoardError::PlayAfterWin(winner) => {
+                write!(f, "{:?} already won, no move can follow", winner)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+/// Formats the grid row by row from the top, e.g. `x.o/.x./...`.
+impl std::fmt::Display for Grid<Tile> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, pos) in self.reading_order().enumerate() {
+            if i > 0 && i % self.cells[0].len() == 0 {
+                write!(f, "/")?;
+            }
+            let symbol = match self.get(pos) {
+                Some(Tile::X) => 'x',
+                Some(Tile::O) => 'o',
+                _ => '.',
+            };
+            write!(f, "{}", symbol)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Grid<Tile> {
+    type Err = String;
+
+    /// Parses the format of [std::fmt::Display], rejecting unreachable boards.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tiles = s
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '/')
+            .map(|c| match c {
+                'x' | 'X' => Ok(Tile::X),
+                'o' | 'O' | '0' => Ok(Tile::O),
+                '.' | '_' | '-' => Ok(Tile::Empty),
+                _ => Err(format!("unexpected symbol {:?}", c)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut grid = Grid::new();
+        let positions: Vec<_> = grid.reading_order().collect();
+        if tiles.len() != positions.len() {
+            return Err(format!(
+                "expected {} cells, got {}",
+                positions.len(),
+                tiles.len()
+            ));
+        }
+        for (pos, tile) in positions.into_iter().zip(tiles) {
+            grid.set(pos, tile);
+        }
+        grid.validate().map_err(|err| err.to_string())?;
+        Ok(grid)
+    }
+}
+
+/// Serializes boards in their text format, for use with `#[serde(with = "board_text")]`.
+pub mod board_text {
+    use super::Grid;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(board: &Grid, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(board)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Grid, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl Grid<f64> {
     pub fn zero() -> Self {
         Self {
@@ -243,3 +339,161 @@ impl MulAssign<Self> for Grid<f64> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    /// The lines of three as indices in reading order, checked independently of [Grid::winner].
+    const LINES: [[usize; 3]; 8] = [
+        [0, 1, 2],
+        [3, 4, 5],
+        [6, 7, 8],
+        [0, 3, 6],
+        [1, 4, 7],
+        [2, 5, 8],
+        [0, 4, 8],
+        [2, 4, 6],
+    ];
+
+    fn grid(tiles: &[Tile; 9]) -> Grid {
+        let mut grid = Grid::new();
+        let positions: Vec<_> = grid.reading_order().collect();
+        for (pos, &tile) in positions.into_iter().zip(tiles) {
+            grid.set(pos, tile);
+        }
+        grid
+    }
+
+    fn has_line(tiles: &[Tile; 9], tile: Tile) -> bool {
+        LINES
+            .iter()
+            .any(|line| line.iter().all(|&i| tiles[i] == tile))
+    }
+
+    fn all_boards() -> Vec<[Tile; 9]> {
+        (0..3usize.pow(9))
+            .map(|i| {
+                std::array::from_fn(|cell| match (i / 3usize.pow(cell as u32)) % 3 {
+                    0 => Tile::Empty,
+                    1 => Tile::X,
+                    _ => Tile::O,
+                })
+            })
+            .collect()
+    }
+
+    /// Every board reached by playing from the empty one until someone wins.
+    fn reachable_boards() -> HashSet<[Tile; 9]> {
+        fn visit(tiles: [Tile; 9], turn: Tile, reached: &mut HashSet<[Tile; 9]>) {
+            if !reached.insert(tiles) || has_line(&tiles, Tile::X) || has_line(&tiles, Tile::O) {
+                return;
+            }
+            let next = if turn == Tile::X { Tile::O } else { Tile::X };
+            for cell in 0..9 {
+                if tiles[cell] == Tile::Empty {
+                    let mut tiles = tiles;
+                    tiles[cell] = turn;
+                    visit(tiles, next, reached);
+                }
+            }
+        }
+
+        let mut reached = HashSet::new();
+        visit([Tile::Empty; 9], Tile::X, &mut reached);
+        reached
+    }
+
+    #[test]
+    fn validate_accepts_exactly_the_reachable_boards() {
+        let reachable = reachable_boards();
+        assert_eq!(reachable.len(), 5478);
+        for tiles in all_boards() {
+            let board = grid(&tiles);
+            assert_eq!(
+                board.validate().is_ok(),
+                reachable.contains(&tiles),
+                "{}: {:?}",
+                board,
+                board.validate()
+            );
+        }
+    }
+
+    #[test]
+    fn validate_reports_why() {
+        let reason = |board: &str| {
+            let tiles: Vec<_> = board.chars().filter(|&c| c != '/').collect();
+            let tiles = std::array::from_fn(|i| match tiles[i] {
+                'x' => Tile::X,
+                'o' => Tile::O,
+                _ => Tile::Empty,
+            });
+            grid(&tiles).validate()
+        };
+        assert_eq!(reason("x.o/.x./..."), Ok(()));
+        assert_eq!(reason("xxx/oo./..."), Ok(()));
+        assert_eq!(reason("xx./.../..."), Err(BoardError::TooManyX));
+        assert_eq!(reason("o../.../..."), Err(BoardError::TooManyO));
+        assert_eq!(reason("xxx/ooo/x.."), Err(BoardError::BothWin));
+        assert_eq!(
+            reason("xxx/oo./o.."),
+            Err(BoardError::PlayAfterWin(Player::X))
+        );
+        assert_eq!(
+            reason("ooo/xx./xx."),
+            Err(BoardError::PlayAfterWin(Player::O))
+        );
+    }
+
+    #[test]
+    fn winner_and_current_player() {
+        for tiles in all_boards() {
+            let board = grid(&tiles);
+            let (x, o) = (has_line(&tiles, Tile::X), has_line(&tiles, Tile::O));
+            let expected = match (x, o) {
+                (true, false) => Some(Player::X),
+                (false, true) => Some(Player::O),
+                _ => None,
+            };
+            assert_eq!(board.winner(), expected, "{}", board);
+
+            let count = |tile| tiles.iter().filter(|&&t| t == tile).count();
+            let expected = match board.validate() {
+                Err(_) => None,
+                Ok(()) if x || o || count(Tile::Empty) == 0 => None,
+                Ok(()) if count(Tile::X) > count(Tile::O) => Some(Player::O),
+                Ok(()) => Some(Player::X),
+            };
+            assert_eq!(board.current_player(), expected, "{}", board);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_unreachable_boards() {
+        for tiles in all_boards() {
+            let board = grid(&tiles);
+            let parsed = board.to_string().parse::<Grid>();
+            match board.validate() {
+                Ok(()) => assert_eq!(parsed, Ok(board)),
+                Err(err) => assert_eq!(parsed, Err(err.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn possible_states_are_reachable() {
+        let reachable = reachable_boards();
+        let hidden: Vec<_> = Grid::new().positions().collect();
+        let states = Grid::new().partial_observation(&hidden).possible_states();
+        assert_eq!(states.len(), reachable.len());
+
+        let board: Grid = "x.o/.x./o..".parse().unwrap();
+        let hidden: Vec<_> = board.empty_positions().collect();
+        for state in board.partial_observation(&hidden).possible_states() {
+            assert_eq!(state.validate(), Ok(()), "{}", state);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use super::{
+    shapley::{factorial, Observation},
+    *,
+};
+
+use std::collections::BTreeMap;
+
+/// A partition of the grid positions into groups of players.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Partition {
+    pub groups: Vec<Vec<vec2<Coord>>>,
+}
+
+impl Partition {
+    pub fn rows() -> Self {
+        let grid = Grid::new();
+        Self {
+            groups: (grid.bounds().min.y..grid.bounds().max.y)
+                .map(|y| grid.positions().filter(|pos| pos.y == y).collect())
+                .collect(),
+        }
+    }
+
+    pub fn columns() -> Self {
+        let grid = Grid::new();
+        Self {
+            groups: (grid.bounds().min.x..grid.bounds().max.x)
+                .map(|x| grid.positions().filter(|pos| pos.x == x).collect())
+                .collect(),
+        }
+    }
+
+    /// The center, the edges and the corners.
+    pub fn rings() -> Self {
+        let grid = Grid::new();
+        let center = grid.bounds().max.map(|x| x / 2);
+        let distance = |pos: vec2<Coord>| pos.x.abs_diff(center.x) + pos.y.abs_diff(center.y);
+        Self {
+            groups: (0..=2)
+                .map(|d| grid.positions().filter(|&pos| distance(pos) == d).collect())
+                .collect(),
+        }
+    }
+
+    /// Checks that every position of the grid is in exactly one group.
+    pub fn check(&self) -> Result<(), String> {
+        let grid = Grid::new();
+        for pos in grid.positions() {
+            let count = self.groups.iter().flatten().filter(|&&p| p == pos).count();
+            if count != 1 {
+                return Err(format!("position {:?} is in {} groups", pos, count));
+            }
+        }
+        if let Some(pos) = self
+            .groups
+            .iter()
+            .flatten()
+            .find(|&&pos| grid.get(pos).is_none())
+        {
+            return Err(format!("position {:?} is outside of the grid", pos));
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Partition {
+    type Err = String;
+
+    /// Parses `rows`, `columns`, `rings`, or cell indices like `0,1,2;3,4,5;6,7,8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let partition =
+            match s {
+                "rows" => Self::rows(),
+                "columns" => Self::columns(),
+                "rings" => Self::rings(),
+                _ => {
+                    let positions: Vec<_> = Grid::new().reading_order().collect();
+                    let groups =
+                        s.split(';')
+                            .map(|group| {
+                                group
+                                    .split(',')
+                                    .map(|index| {
+                                        let index: usize = index.trim().parse().map_err(|_| {
+                                            format!("invalid cell index {:?}", index)
+                                        })?;
+                                        positions.get(index).copied().ok_or_else(|| {
+                                            format!("cell index {} is too large", index)
+                                        })
+                                    })
+                                    .collect::<Result<Vec<_>, _>>()
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                    Self { groups }
+                }
+            };
+        partition.check()?;
+        Ok(partition)
+    }
+}
+
+/// Attributions of a game with a coalition structure.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Owen<T> {
+    /// Shapley values of the groups, each playing as a single player.
+    pub groups: Vec<T>,
+    /// Owen values of the cells, which sum up to the value of their group.
+    pub cells: Grid<T>,
+}
+
+impl Grid<Tile> {
+    pub fn owen(&self, partition: &Partition, policy: &mut Policy) -> Owen<Grid<f64>> {
+        self.owen_with_value(partition, |observation| observation.value(policy))
+    }
+
+    /// Owen values of SVERL-P local, rolled out against the opponent if given.
+    pub fn sverl_owen(
+        &self,
+        partition: &Partition,
+        gamma: f64,
+        policy: &mut Policy,
+        mut opponent: Option<&mut Policy>,
+    ) -> Owen<f64> {
+        let Some(player) = self.current_player() else {
+            return Owen {
+                groups: vec![0.0; partition.groups.len()],
+                cells: Grid::zero(),
+            };
+        };
+
+        let mut cache = BTreeMap::new();
+        let values = self.owen_with_value(partition, |observation| {
+            let opponent = opponent.as_deref_mut();
+            self.sverl_local_value(player, observation, &mut cache, gamma, policy, opponent)
+        });
+        Owen {
+            groups: values.groups.iter().map(|value| value.sum()).collect(),
+            cells: Grid::from_fn(|pos| values.cells.get(pos).unwrap().sum()),
+        }
+    }
+
+    /// Computes the Owen values (Owen, 1977) of the cells given a partition into groups,
+    /// together with the Shapley values of the quotient game played by the groups.
+    ///
+    /// For a cell `i` in group `k`:
+    /// `phi(i) = sum over R in M \ {k}, T in B_k \ {i} of w(|R|, m) * w(|T|, b_k) *
+    /// (v(Q_R + T + i) - v(Q_R + T))`,
+    /// where `Q_R` is the union of the groups in `R` and `w(s, n) = s! (n - s - 1)! / n!`.
+    ///
+    /// Panics if `partition` is not a partition of the grid positions.
+    pub fn owen_with_value(
+        &self,
+        partition: &Partition,
+        mut value: impl FnMut(&Observation) -> Grid<f64>,
+    ) -> Owen<Grid<f64>> {
+        if let Err(err) = partition.check() {
+            panic!("invalid partition: {}", err);
+        }
+
+        let positions: Vec<_> = self.positions().collect();
+        let bit = |pos: vec2<Coord>| 1usize << positions.iter().position(|&p| p == pos).unwrap();
+        let groups: Vec<Vec<usize>> = partition
+            .groups
+            .iter()
+            .map(|group| group.iter().map(|&pos| bit(pos)).collect())
+            .collect();
+        let group_masks: Vec<usize> = groups.iter().map(|group| group.iter().sum()).collect();
+        let m = groups.len();
+
+        let mut cache = HashMap::<usize, Grid<f64>>::new();
+        let mut value = move |mask: usize| {
+            if let Some(cached) = cache.get(&mask) {
+                return cached.clone();
+            }
+            let res = value(&self.mask_observation(mask));
+            cache.insert(mask, res.clone());
+            res
+        };
+
+        let weight = |s: usize, n: usize| {
+            factorial(s) as f64 * factorial(n - s - 1) as f64 / factorial(n) as f64
+        };
+        // Subsets of the other groups with their union and weight
+        let other_groups = |k: usize| {
+            let group_masks = &group_masks;
+            (0..1usize << m)
+                .filter(move |r| (r >> k).is_multiple_of(2))
+                .map(move |r| {
+                    let union = (0..m)
+                        .filter(|l| (r >> l) % 2 == 1)
+                        .map(|l| group_masks[l])
+                        .sum::<usize>();
+                    (union, weight(r.count_ones() as usize, m))
+                })
+        };
+
+        let group_values = (0..m)
+            .map(|k| {
+                other_groups(k)
+                    .map(|(union, w)| {
+                        let mut value = value(union | group_masks[k]) - value(union);
+                        value *= w;
+                        value
+                    })
+                    .fold(Grid::zero(), Grid::add)
+            })
+            .collect();
+
+        let cells = Grid::from_fn(|pos| {
+            let i = bit(pos);
+            let k = groups.iter().position(|group| group.contains(&i)).unwrap();
+            let members: Vec<usize> = groups[k].iter().copied().filter(|&j| j != i).collect();
+            let b = groups[k].len();
+
+            let mut result = Grid::zero();
+            for (union, w_groups) in other_groups(k) {
+                for t in 0..1usize << members.len() {
+                    let inside = (0..members.len())
+                        .filter(|l| (t >> l) % 2 == 1)
+                        .map(|l| members[l])
+                        .sum::<usize>();
+                    let w = w_groups * weight(t.count_ones() as usize, b);
+
+                    let mut value = value(union | inside | i) - value(union | inside);
+                    value *= w;
+                    result = result + value;
+                }
+            }
+            result
+        });
+
+        Owen {
+            groups: group_values,
+            cells,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_shapley(partition: &Partition) {
+        let board: Grid = "x../.o./...".parse().unwrap();
+        let mut cache = MinimaxCache::new();
+        let mut policy = policy_minimax_cached(None, &mut cache);
+        let owen = board.owen(partition, &mut policy);
+        let shapley = board.shapley(&mut policy);
+        for pos in board.positions() {
+            let (owen, shapley) = (owen.cells.get(pos).unwrap(), shapley.get(pos).unwrap());
+            for action in board.positions() {
+                let (owen, shapley) = (owen.get(action).unwrap(), shapley.get(action).unwrap());
+                assert!(
+                    (owen - shapley).abs() < 1e-9,
+                    "cell {:?}, action {:?}: owen {}, shapley {}",
+                    pos,
+                    action,
+                    owen,
+                    shapley
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn owen_of_singletons_is_shapley() {
+        let groups = Grid::new().positions().map(|pos| vec![pos]).collect();
+        assert_shapley(&Partition { groups });
+    }
+
+    #[test]
+    fn owen_of_one_group_is_shapley() {
+        let groups = vec![Grid::new().positions().collect()];
+        assert_shapley(&Partition { groups });
+    }
+}
@@ -4,9 +4,18 @@ use super::*;
 
 pub type Policy<'a> = Box<dyn FnMut(&Grid) -> Grid<f64> + 'a>;
 pub type Action = vec2<Coord>;
+/// Minimax values by board and depth limit, which may be shared by searches of any limit.
+/// Policies used at the same time, such as an opponent in rollouts, need caches of their own.
+pub type MinimaxCache = BTreeMap<(Grid, Option<usize>), Grid<f64>>;
+
+/// The random number generator for the seed, or for a fresh one, together with the seed.
+pub fn seeded_rng(seed: Option<u64>) -> (u64, StdRng) {
+    let seed = seed.unwrap_or_else(|| thread_rng().gen());
+    (seed, StdRng::seed_from_u64(seed))
+}
 
-fn choose_action(probs: Grid<f64>) -> Action {
-    let mut rng: f64 = thread_rng().gen();
+pub(super) fn choose_action(probs: Grid<f64>, rng: &mut impl Rng) -> Action {
+    let mut rng: f64 = rng.gen();
     let mut last = vec2::ZERO;
     for pos in probs.positions() {
         let &p = probs.get(pos).unwrap();
@@ -23,9 +32,9 @@ fn choose_action(probs: Grid<f64>) -> Action {
     last
 }
 
-pub fn random_action(grid: &Grid) -> Action {
+pub fn random_action(grid: &Grid, rng: &mut impl Rng) -> Action {
     let probs = policy_random()(grid);
-    choose_action(probs)
+    choose_action(probs, rng)
 }
 
 pub fn policy_random() -> Policy<'static> {
@@ -50,10 +59,7 @@ pub fn policy_random() -> Policy<'static> {
 //     })
 // }
 
-pub fn policy_minimax_cached(
-    depth: Option<usize>,
-    cache: &mut BTreeMap<Grid, Grid<f64>>,
-) -> Policy<'_> {
+pub fn policy_minimax_cached(depth: Option<usize>, cache: &mut MinimaxCache) -> Policy<'_> {
     Box::new(move |grid| {
         let Some(player) = grid.current_player() else {
             return Grid::zero();
@@ -64,13 +70,14 @@ pub fn policy_minimax_cached(
 
 pub fn minimax_action(
     grid: &Grid,
-    cache: &mut BTreeMap<Grid, Grid<f64>>,
+    cache: &mut MinimaxCache,
     player: Player,
     limit: Option<usize>,
+    rng: &mut impl Rng,
 ) -> (Action, f64) {
     let probs = minimax_probability(grid, cache, player, limit);
-    let values = minimax(grid, cache, player, limit, 0);
-    let action = choose_action(probs);
+    let values = minimax(grid, cache, player, limit);
+    let action = choose_action(probs, rng);
     let mut value = *values.get(action).unwrap();
     if value.abs() <= 1e-5 {
         value = 0.0;
@@ -78,13 +85,33 @@ pub fn minimax_action(
     (action, value)
 }
 
+/// The value of the best action for the player.
+pub fn minimax_value(
+    grid: &Grid,
+    cache: &mut MinimaxCache,
+    player: Player,
+    limit: Option<usize>,
+) -> f64 {
+    let values = minimax(grid, cache, player, limit);
+    let value = grid
+        .empty_positions()
+        .map(|pos| *values.get(pos).unwrap())
+        .max_by_key(|&v| r64(v))
+        .unwrap_or(0.0);
+    if value.abs() <= 1e-5 {
+        0.0
+    } else {
+        value
+    }
+}
+
 pub fn minimax_probability(
     grid: &Grid,
-    cache: &mut BTreeMap<Grid, Grid<f64>>,
+    cache: &mut MinimaxCache,
     player: Player,
     limit: Option<usize>,
 ) -> Grid<f64> {
-    let values = minimax(grid, cache, player, limit, 0);
+    let values = minimax(grid, cache, player, limit);
     let max_value = values
         .positions()
         .filter(|&pos| grid.check(pos))
@@ -101,22 +128,23 @@ pub fn minimax_probability(
     .normalize()
 }
 
+/// Values of every action for the player, where a game won `k` moves later is worth `1 / (k + 1)`.
 pub fn minimax(
     grid: &Grid,
-    cache: &mut BTreeMap<Grid, Grid<f64>>,
+    cache: &mut MinimaxCache,
     player: Player,
     limit: Option<usize>,
-    depth: usize,
 ) -> Grid<f64> {
     // log::debug!(
-    //     "[depth {}] minimax for player {:?} at {:?}",
-    //     depth,
+    //     "[limit {:?}] minimax for player {:?} at {:?}",
+    //     limit,
     //     player,
     //     grid
     // );
 
-    if let Some(cached) = cache.get(grid) {
-        // log::debug!("[depth {}] cached: {:?}", depth, cached);
+    let key = (grid.clone(), limit);
+    if let Some(cached) = cache.get(&key) {
+        // log::debug!("[limit {:?}] cached: {:?}", limit, cached);
         return cached.clone();
     }
 
@@ -128,28 +156,74 @@ pub fn minimax(
         let mut grid = grid.clone();
         grid.set(action, player.into());
 
-        // log::debug!("[depth {}] evaluating move {:?}", depth, action);
+        // log::debug!("[limit {:?}] evaluating move {:?}", limit, action);
 
         let value = grid.reward(player);
-        if value != 0.0 || limit.map_or(false, |limit| depth >= limit) {
+        if value != 0.0 || limit == Some(0) {
             // Game finished
-            // log::debug!("[depth {}] game ended {:.2}", depth, value);
-            return value / (depth + 1) as f64;
+            // log::debug!("[limit {:?}] game ended {:.2}", limit, value);
+            return value;
         }
 
         // Recursion
-        let deep = minimax(&grid, cache, player.next(), limit, depth + 1);
-        let value = deep
-            .cells
-            .into_iter()
-            .flatten()
-            .map(r64)
+        let deep = minimax(&grid, cache, player.next(), limit.map(|limit| limit - 1));
+        // Only the opponent's legal moves, occupied cells would count as draws
+        let value = grid
+            .empty_positions()
+            .map(|pos| r64(*deep.get(pos).unwrap()))
             .max()
-            .unwrap()
-            .raw();
-        -value
+            .map_or(0.0, |value| value.raw());
+        -delay(value)
     });
-    cache.insert(grid.clone(), res.clone());
-    // log::debug!("[depth {}] result: {:?}", depth, res);
+    cache.insert(key, res.clone());
+    // log::debug!("[limit {:?}] result: {:?}", limit, res);
     res
 }
+
+/// The value of an outcome one move further away: `1 / (k + 1)` becomes `1 / (k + 2)`.
+fn delay(value: f64) -> f64 {
+    if value == 0.0 {
+        0.0
+    } else {
+        value.signum() / (value.abs().recip() + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimax_avoids_forced_losses() {
+        // After X takes a corner, every reply but the center loses to a fork
+        let grid: Grid = "x../.../...".parse().unwrap();
+        let probabilities = minimax_probability(&grid, &mut BTreeMap::new(), Player::O, None);
+        for (i, pos) in grid.reading_order().enumerate() {
+            let expected = if i == 4 { 1.0 } else { 0.0 };
+            assert_eq!(*probabilities.get(pos).unwrap(), expected, "cell {}", i);
+        }
+    }
+
+    #[test]
+    fn minimax_sees_forced_wins() {
+        // O answered the corner with an edge, which X wins by forking
+        let grid: Grid = "xo./.../...".parse().unwrap();
+        let value = minimax_value(&grid, &mut BTreeMap::new(), Player::X, None);
+        assert!(value > 0.0, "value {}", value);
+    }
+
+    #[test]
+    fn minimax_cache_is_shared_by_limits() {
+        // Searches of different limits and from different roots share one cache
+        let grid: Grid = "x../.o./...".parse().unwrap();
+        let mut cache = MinimaxCache::new();
+        for limit in [None, Some(1), Some(3)] {
+            minimax(&Grid::new(), &mut cache, Player::X, limit);
+        }
+        for limit in [None, Some(0), Some(1), Some(2), Some(3)] {
+            let shared = minimax(&grid, &mut cache, Player::X, limit);
+            let fresh = minimax(&grid, &mut MinimaxCache::new(), Player::X, limit);
+            assert_eq!(shared, fresh, "limit {:?}", limit);
+        }
+    }
+}
@@ -6,6 +6,89 @@ pub struct Observation {
     pub grid: Grid,
 }
 
+/// Weighting of the marginal contributions of a feature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Semivalue {
+    Shapley,
+    Banzhaf,
+    /// `alpha > beta` favours small coalitions, `alpha = beta = 1` is Shapley.
+    BetaShapley {
+        alpha: f64,
+        beta: f64,
+    },
+}
+
+impl Semivalue {
+    /// The weight of a single coalition of `s` out of the other `n - 1` features.
+    pub fn weight(self, n: usize, s: usize) -> f64 {
+        let others = n - 1;
+        match self {
+            Semivalue::Shapley => {
+                factorial(s) as f64 * factorial(others - s) as f64 / factorial(n) as f64
+            }
+            Semivalue::Banzhaf => 0.5f64.powi(others as i32),
+            Semivalue::BetaShapley { alpha, beta } => {
+                rising(beta, s) * rising(alpha, others - s) / rising(alpha + beta, others)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Semivalue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Semivalue::Shapley => write!(f, "shapley"),
+            Semivalue::Banzhaf => write!(f, "banzhaf"),
+            Semivalue::BetaShapley { alpha, beta } => write!(f, "beta:{},{}", alpha, beta),
+        }
+    }
+}
+
+impl std::str::FromStr for Semivalue {
+    type Err = String;
+
+    /// Parses `shapley`, `banzhaf`, or `beta:<alpha>,<beta>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "shapley" => Ok(Semivalue::Shapley),
+            "banzhaf" => Ok(Semivalue::Banzhaf),
+            s => {
+                let params = s
+                    .strip_prefix("beta:")
+                    .ok_or_else(|| format!("unknown weighting {:?}", s))?;
+                let (alpha, beta) = params
+                    .split_once(',')
+                    .ok_or_else(|| format!("expected `beta:<alpha>,<beta>`, got {:?}", s))?;
+                let parse = |x: &str| {
+                    x.trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|&x| x > 0.0)
+                        .ok_or_else(|| format!("invalid beta parameter {:?}", x))
+                };
+                Ok(Semivalue::BetaShapley {
+                    alpha: parse(alpha)?,
+                    beta: parse(beta)?,
+                })
+            }
+        }
+    }
+}
+
+/// Serialized in the text format of [FromStr](std::str::FromStr).
+impl Serialize for Semivalue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Semivalue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl Grid<Tile> {
     pub fn full_observation(&self) -> Observation {
         Observation {
@@ -14,11 +97,34 @@ impl Grid<Tile> {
         }
     }
 
+    /// Observation of the grid with the given positions hidden.
+    pub fn partial_observation(&self, hidden: &[vec2<Coord>]) -> Observation {
+        Observation {
+            positions: self
+                .positions()
+                .filter(|pos| !hidden.contains(pos))
+                .collect(),
+            grid: self.clone(),
+        }
+    }
+
+    /// Observation of the positions selected by the bits of `mask`, in [Grid::positions] order.
+    pub(super) fn mask_observation(&self, mask: usize) -> Observation {
+        Observation {
+            positions: self
+                .positions()
+                .enumerate()
+                .filter(|&(t, _)| (mask >> t) % 2 == 1)
+                .map(|(_, pos)| pos)
+                .collect(),
+            grid: self.clone(),
+        }
+    }
+
     pub fn all_subsets(&self) -> Vec<Observation> {
         let positions: Vec<_> = self.positions().collect();
         powerset(&positions)
             .into_iter()
-            .skip(1) // Skip the set itself
             .map(|positions| Observation {
                 positions,
                 grid: self.clone(),
@@ -27,47 +133,72 @@ impl Grid<Tile> {
     }
 
     pub fn shapley(&self, policy: &mut Policy) -> Grid<Grid<f64>> {
-        self.shapley_with_value(|_feature, observation| observation.value(policy))
+        self.semivalue(Semivalue::Shapley, policy)
+    }
+
+    pub fn semivalue(&self, semivalue: Semivalue, policy: &mut Policy) -> Grid<Grid<f64>> {
+        self.semivalue_with_value(semivalue, |observation| observation.value(policy))
     }
 
-    pub fn shapley_with_value(
+    /// Semivalues of a characteristic function shared by all features.
+    pub fn semivalue_with_value(
         &self,
-        mut value: impl FnMut(vec2<Coord>, &Observation) -> Grid<f64>,
+        semivalue: Semivalue,
+        mut value: impl FnMut(&Observation) -> Grid<f64>,
     ) -> Grid<Grid<f64>> {
-        let subsets = self.all_subsets();
-        let n = self.positions().count();
-        let scale = (factorial(n) as f64).recip();
-
         let mut cache = HashMap::<Observation, Grid<f64>>::new();
-        let mut value = move |feature: vec2<Coord>, observation: &Observation| {
+        self.semivalue_uncached(semivalue, |_feature, observation| {
             if let Some(cached) = cache.get(observation) {
                 return cached.clone();
             }
-            let res = value(feature, observation);
+            let res = value(observation);
             cache.insert(observation.clone(), res.clone());
             res
-        };
+        })
+    }
+
+    /// Semivalues where every feature has its own characteristic function.
+    pub fn semivalue_with_feature_value(
+        &self,
+        semivalue: Semivalue,
+        mut value: impl FnMut(vec2<Coord>, &Observation) -> Grid<f64>,
+    ) -> Grid<Grid<f64>> {
+        let mut cache = HashMap::<(vec2<Coord>, Observation), Grid<f64>>::new();
+        self.semivalue_uncached(semivalue, |feature, observation| {
+            let key = (feature, observation.clone());
+            if let Some(cached) = cache.get(&key) {
+                return cached.clone();
+            }
+            let res = value(feature, observation);
+            cache.insert(key, res.clone());
+            res
+        })
+    }
+
+    fn semivalue_uncached(
+        &self,
+        semivalue: Semivalue,
+        mut value: impl FnMut(vec2<Coord>, &Observation) -> Grid<f64>,
+    ) -> Grid<Grid<f64>> {
+        let subsets = self.all_subsets();
+        let n = self.positions().count();
 
         Grid::from_fn(|feature| {
-            let mut result = subsets
+            subsets
                 .iter()
-                .cloned()
                 .map(|observation| {
-                    let base_value = value(feature, &observation);
-                    let s = observation.positions.len();
-
                     let mut featureless = observation.clone();
                     if !featureless.subtract(feature) {
                         return Grid::zero();
                     }
 
-                    let mut value = base_value - value(feature, &featureless);
-                    value *= factorial(s) as f64 * factorial(n - s - 1) as f64;
+                    // Weighted by the size of the coalition the feature joins
+                    let s = featureless.positions.len();
+                    let mut value = value(feature, observation) - value(feature, &featureless);
+                    value *= semivalue.weight(n, s);
                     value
                 })
-                .fold(Grid::zero(), Grid::add);
-            result *= scale;
-            result
+                .fold(Grid::zero(), Grid::add)
         })
     }
 }
@@ -82,6 +213,7 @@ impl Observation {
         }
     }
 
+    /// The boards that agree with the observed cells and can be reached in a game.
     pub fn possible_states(&self) -> Vec<Grid> {
         let hidden: Vec<_> = self
             .grid
@@ -105,6 +237,7 @@ impl Observation {
                 }
                 grid
             })
+            .filter(|grid| grid.validate().is_ok())
             .collect()
     }
 
@@ -137,6 +270,46 @@ where
         .collect()
 }
 
-fn factorial(x: usize) -> usize {
-    (2..=x).fold(1, usize::mul)
+pub(super) fn factorial(x: usize) -> usize {
+    (2..=x).product()
+}
+
+/// The rising factorial `x (x + 1) ... (x + k - 1)`.
+fn rising(x: f64, k: usize) -> f64 {
+    (0..k).map(|i| x + i as f64).product()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binomial(n: usize, k: usize) -> f64 {
+        factorial(n) as f64 / (factorial(k) as f64 * factorial(n - k) as f64)
+    }
+
+    #[test]
+    fn shapley_weights_sum_to_one() {
+        // Over the subsets of the other features, grouped by size
+        for n in 1..=9 {
+            let total: f64 = (0..n)
+                .map(|s| binomial(n - 1, s) * Semivalue::Shapley.weight(n, s))
+                .sum();
+            assert!(
+                (total - 1.0).abs() < 1e-9,
+                "weights of {} features sum to {}",
+                n,
+                total
+            );
+        }
+    }
+
+    #[test]
+    fn banzhaf_weights_coalitions_equally() {
+        for n in 1..=9 {
+            for s in 0..n {
+                let expected = 0.5f64.powi(n as i32 - 1);
+                assert!((Semivalue::Banzhaf.weight(n, s) - expected).abs() < 1e-12);
+            }
+        }
+    }
 }
@@ -1,59 +1,165 @@
-use super::*;
+use super::{shapley::Observation, *};
 
 use std::collections::BTreeMap;
 
+/// Plays `player`'s moves with `policy`, and the other's with `opponent` if given.
+fn rollout_policy<'a>(
+    player: Player,
+    policy: &'a mut Policy,
+    mut opponent: Option<&'a mut Policy>,
+) -> Policy<'a> {
+    Box::new(move |grid: &Grid| match &mut opponent {
+        Some(opponent) if grid.current_player() != Some(player) => opponent(grid),
+        _ => policy(grid),
+    })
+}
+
 impl Grid<Tile> {
-    pub fn sverl(&self, global: bool, gamma: f64, policy: &mut Policy) -> Grid<f64> {
+    /// SVERL-P of the policy, rolled out against `opponent` or in self-play.
+    pub fn sverl(
+        &self,
+        global: bool,
+        gamma: f64,
+        policy: &mut Policy,
+        opponent: Option<&mut Policy>,
+    ) -> Grid<f64> {
+        self.sverl_semivalue(Semivalue::Shapley, global, gamma, policy, opponent)
+    }
+
+    pub fn sverl_semivalue(
+        &self,
+        semivalue: Semivalue,
+        global: bool,
+        gamma: f64,
+        policy: &mut Policy,
+        mut opponent: Option<&mut Policy>,
+    ) -> Grid<f64> {
         let Some(player) = self.current_player() else {
             return Grid::zero();
         };
 
-        let mut cache = BTreeMap::new();
-        let values = self.shapley_with_value(|feature, observation| {
+        if !global {
+            let mut cache = BTreeMap::new();
+            let values = self.semivalue_with_value(semivalue, |observation| {
+                self.sverl_local_value(
+                    player,
+                    observation,
+                    &mut cache,
+                    gamma,
+                    policy,
+                    opponent.as_deref_mut(),
+                )
+            });
+            return Grid::from_fn(|pos| values.get(pos).unwrap().sum());
+        }
+
+        // The rollouts of the global variant depend on the feature, so they are cached separately
+        let mut global_caches: HashMap<vec2<Coord>, BTreeMap<Grid, f64>> = HashMap::new();
+        let values = self.semivalue_with_feature_value(semivalue, |feature, observation| {
             let first = observation.value(policy);
+            let cache = global_caches.entry(feature).or_default();
+            self.sverl_value(player, &first, gamma, |grid| {
+                let mut policy = Box::new(|state: &Grid| {
+                    let mut observation = state.full_observation();
+                    let sub = observation.subtract(feature);
+                    assert!(sub, "Full observation does not have the feature");
+                    observation.value(policy)
+                }) as Policy;
+                let mut rollout = rollout_policy(player, &mut policy, opponent.as_deref_mut());
+                grid.predict(cache, gamma, player, &mut rollout)
+            })
+        });
+        Grid::from_fn(|pos| values.get(pos).unwrap().sum())
+    }
+
+    /// Attributes the state value `V(s)` of the policy to the cells.
+    pub fn sverl_state_value(
+        &self,
+        semivalue: Semivalue,
+        gamma: f64,
+        policy: &mut Policy,
+    ) -> Grid<f64> {
+        let Some(player) = self.current_player() else {
+            return Grid::zero();
+        };
 
-            let mut result = Grid::zero();
-            for pos in self.empty_positions() {
-                let prob = match first.get(pos) {
-                    Some(&p) if p > 0.0 => p,
-                    _ => continue,
-                };
-
-                let mut grid = self.clone();
-                grid.set(pos, player.into());
-                let value = if global {
-                    let mut policy = Box::new(|state: &Grid| {
-                        let mut observation = state.full_observation();
-                        let sub = observation.subtract(feature);
-                        assert!(sub, "Full observation does not have the feature");
-                        observation.value(policy)
-                    }) as Policy;
-                    grid.predict(&mut cache, gamma, &mut policy)
-                } else {
-                    grid.predict(&mut cache, gamma, policy)
-                };
-                result.set(pos, prob * value);
-            }
-            result
+        let mut cache = BTreeMap::new();
+        let values = self.semivalue_with_value(semivalue, |observation| {
+            observation.state_value(&mut cache, gamma, player, policy)
         });
         Grid::from_fn(|pos| values.get(pos).unwrap().sum())
     }
 
-    fn predict(
+    /// The SVERL-P local characteristic function.
+    pub(super) fn sverl_local_value(
+        &self,
+        player: Player,
+        observation: &Observation,
+        cache: &mut BTreeMap<Grid<Tile>, f64>,
+        gamma: f64,
+        policy: &mut Policy,
+        opponent: Option<&mut Policy>,
+    ) -> Grid<f64> {
+        let first = observation.value(policy);
+        let mut rollout = rollout_policy(player, policy, opponent);
+        self.sverl_value(player, &first, gamma, |grid| {
+            grid.predict(cache, gamma, player, &mut rollout)
+        })
+    }
+
+    /// The expected return of each action when acting according to `first`.
+    pub(super) fn sverl_value(
+        &self,
+        player: Player,
+        first: &Grid<f64>,
+        gamma: f64,
+        mut predict: impl FnMut(&Grid) -> f64,
+    ) -> Grid<f64> {
+        let mut result = Grid::zero();
+        for pos in self.empty_positions() {
+            let prob = match first.get(pos) {
+                Some(&p) if p > 0.0 => p,
+                _ => continue,
+            };
+
+            let mut grid = self.clone();
+            grid.set(pos, player.into());
+            result.set(pos, prob * (grid.reward(player) + gamma * predict(&grid)));
+        }
+        result
+    }
+
+    /// The expected return of the rollout from this state for `perspective`.
+    /// The cache must only be shared between calls with the same perspective and policy.
+    pub(super) fn predict(
         &self,
         cache: &mut BTreeMap<Grid<Tile>, f64>,
         gamma: f64,
+        perspective: Player,
         policy: &mut Policy,
     ) -> f64 {
         if let Some(&cached) = cache.get(self) {
             return cached;
         }
 
+        let result = self.action_values(cache, gamma, perspective, policy).sum();
+        cache.insert(self.clone(), result);
+        result
+    }
+
+    /// Probability times return of each action, for `perspective`.
+    pub(super) fn action_values(
+        &self,
+        cache: &mut BTreeMap<Grid<Tile>, f64>,
+        gamma: f64,
+        perspective: Player,
+        policy: &mut Policy,
+    ) -> Grid<f64> {
+        let mut result = Grid::zero();
         let Some(player) = self.current_player() else {
-            return 0.0;
+            return result;
         };
 
-        let mut result = 0.0;
         let weights = policy(self);
         for pos in self.empty_positions() {
             let prob = match weights.get(pos) {
@@ -64,12 +170,162 @@ impl Grid<Tile> {
             let mut grid = self.clone();
             grid.set(pos, player.into());
 
-            let immediate_reward = grid.reward(player);
-            let future_reward = gamma * grid.predict(cache, gamma, policy);
-            result += prob * (immediate_reward + future_reward);
+            let immediate_reward = grid.reward(perspective);
+            let future_reward = gamma * grid.predict(cache, gamma, perspective, policy);
+            result.set(pos, prob * (immediate_reward + future_reward));
         }
+        result
+    }
+}
 
-        cache.insert(self.clone(), result);
+impl Observation {
+    /// [Grid::action_values] averaged over the states consistent with the observation.
+    pub(super) fn state_value(
+        &self,
+        cache: &mut BTreeMap<Grid<Tile>, f64>,
+        gamma: f64,
+        perspective: Player,
+        policy: &mut Policy,
+    ) -> Grid<f64> {
+        let states = self.possible_states();
+        let prob = (states.len() as f64).recip();
+
+        let mut result = states
+            .iter()
+            .map(|state| state.action_values(cache, gamma, perspective, policy))
+            .fold(Grid::zero(), Grid::add);
+        result *= prob;
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMMA: f64 = 0.9;
+
+    /// Checks the values of the cells given by their index in reading order, the others being 0.
+    fn assert_values(values: &Grid<f64>, expected: &[(usize, f64)]) {
+        for (i, pos) in values.reading_order().enumerate() {
+            let expected = expected
+                .iter()
+                .find(|&&(index, _)| index == i)
+                .map_or(0.0, |&(_, value)| value);
+            let value = *values.get(pos).unwrap();
+            assert!(
+                (value - expected).abs() < 1e-9,
+                "cell {}: expected {}, got {}",
+                i,
+                expected,
+                value
+            );
+        }
+    }
+
+    /// X to move: cell 8 wins, after cell 6 O can win at 7,
+    /// after cell 7 O has to block at 8 or lose to X at 8.
+    fn x_to_move() -> Grid {
+        "xox/oox/...".parse().unwrap()
+    }
+
+    /// O to move: cell 8 wins, cell 0 lets X win at 8.
+    fn o_to_move() -> Grid {
+        ".xo/xoo/xx.".parse().unwrap()
+    }
+
+    #[test]
+    fn random_returns_for_the_first_player() {
+        // Q(6) = γ (½ · -1 + ½ · 0), Q(7) = γ (½ · γ · 1 + ½ · 0), Q(8) = 1
+        let values =
+            x_to_move().action_values(&mut BTreeMap::new(), GAMMA, Player::X, &mut policy_random());
+        assert_values(
+            &values,
+            &[
+                (6, -GAMMA / 2.0 / 3.0),
+                (7, GAMMA * GAMMA / 2.0 / 3.0),
+                (8, 1.0 / 3.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn random_returns_for_the_second_player() {
+        // Q(0) = γ · -1, Q(8) = 1
+        let values =
+            o_to_move().action_values(&mut BTreeMap::new(), GAMMA, Player::O, &mut policy_random());
+        assert_values(&values, &[(0, -GAMMA / 2.0), (8, 0.5)]);
+    }
+
+    #[test]
+    fn full_observation_value_is_the_expected_return() {
+        let board = x_to_move();
+        let full = board.full_observation();
+        let value = board.sverl_local_value(
+            Player::X,
+            &full,
+            &mut BTreeMap::new(),
+            GAMMA,
+            &mut policy_random(),
+            None,
+        );
+        assert_values(
+            &value,
+            &[
+                (6, -GAMMA / 2.0 / 3.0),
+                (7, GAMMA * GAMMA / 2.0 / 3.0),
+                (8, 1.0 / 3.0),
+            ],
+        );
+
+        let mut cache = BTreeMap::new();
+        let value = board.sverl_local_value(
+            Player::X,
+            &full,
+            &mut BTreeMap::new(),
+            GAMMA,
+            &mut policy_minimax_cached(None, &mut cache),
+            None,
+        );
+        assert_values(&value, &[(8, 1.0)]);
+    }
+
+    #[test]
+    fn opponent_plays_the_rollouts() {
+        // Minimax O wins at 7 after cell 6 and blocks at 8 after cell 7
+        let board = x_to_move();
+        let mut cache = BTreeMap::new();
+        let mut opponent = policy_minimax_cached(None, &mut cache);
+        let value = board.sverl_local_value(
+            Player::X,
+            &board.full_observation(),
+            &mut BTreeMap::new(),
+            GAMMA,
+            &mut policy_random(),
+            Some(&mut opponent),
+        );
+        assert_values(&value, &[(6, -GAMMA / 3.0), (8, 1.0 / 3.0)]);
+    }
+
+    #[test]
+    fn global_rollouts_hide_their_own_feature() {
+        // O to move: after cell 8 X wins at 2, after cell 2 X draws at 8,
+        // so only the return of cell 8 is not 0: γ · -P(X plays 2 with the feature hidden).
+        // Hiding an X makes the board unreachable, so X still plays 2.
+        // Hiding an O also allows the board without it, where X plays 2 half of the time,
+        // and hiding cell 2 also allows the finished board, where X does not move.
+        let board: Grid = "xx./oox/xo.".parse().unwrap();
+        let hidden_return = [1.0, 1.0, 0.5, 0.75, 0.75, 1.0, 1.0, 0.75, 0.75];
+
+        let shapley = board.shapley(&mut policy_random());
+        let values = board.sverl(true, GAMMA, &mut policy_random(), None);
+        let cell_8 = board.reading_order().nth(8).unwrap();
+        let expected: Vec<_> = board
+            .reading_order()
+            .zip(hidden_return)
+            .map(|(pos, p)| -GAMMA * p * shapley.get(pos).unwrap().get(cell_8).unwrap())
+            .enumerate()
+            .collect();
+        assert_values(&values, &expected);
+    }
+}